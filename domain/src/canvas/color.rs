@@ -0,0 +1,76 @@
+//! Conversions between sRGB encoded `Color32` and the linear colours used for shading.
+//! Everything the renderer adds or multiplies is linear, sRGB only appears at the edges:
+//! colours picked in the UI are decoded once and the framebuffer is encoded once.
+
+use egui::Color32;
+use glam::{Vec3, Vec4};
+
+/// sRGB transfer function, `[0, 1]` encoded value to linear
+#[inline]
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Inverse of [`srgb_to_linear`]
+#[inline]
+pub fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Decodes a colour to linear RGB, ignoring its alpha
+#[inline]
+pub fn to_linear_rgb(color: Color32) -> Vec3 {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    Vec3::new(r as f32, g as f32, b as f32).map(|x| srgb_to_linear(x / 255.0))
+}
+
+/// Decodes a colour to linear RGB with unmultiplied alpha
+#[inline]
+pub fn to_linear(color: Color32) -> Vec4 {
+    to_linear_rgb(color).extend(color.a() as f32 / 255.0)
+}
+
+/// Encodes linear RGB with unmultiplied alpha, channels are clamped to `[0, 1]`
+pub fn to_color32(col: Vec4) -> Color32 {
+    let encode = |x: f32| (linear_to_srgb(x.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    Color32::from_rgba_unmultiplied(
+        encode(col.x),
+        encode(col.y),
+        encode(col.z),
+        (col.w.clamp(0.0, 1.0) * 255.0).round() as u8,
+    )
+}
+
+/// Encodes premultiplied linear RGBA the way egui expects it, channels are clamped to `[0, 1]`
+pub fn to_color32_premultiplied(col: Vec4) -> Color32 {
+    let encode = |x: f32| (linear_to_srgb(x.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    Color32::from_rgba_premultiplied(
+        encode(col.x),
+        encode(col.y),
+        encode(col.z),
+        (col.w.clamp(0.0, 1.0) * 255.0).round() as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for x in 0..=255u8 {
+            let col = Color32::from_rgb(x, x / 2, 255 - x);
+            assert_eq!(to_color32(to_linear(col)), col);
+        }
+        assert!((to_linear_rgb(Color32::from_gray(188)).x - 0.5).abs() < 0.01);
+        assert!((srgb_to_linear(linear_to_srgb(0.2)) - 0.2).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,213 @@
+use egui::{Color32, ColorImage};
+use glam::{Vec3, Vec4, Vec4Swizzles};
+
+use super::ToneMapping;
+
+/// Linear HDR image with premultiplied alpha the renderers draw into
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec4>,
+    /// Distance along the camera ray to the nearest surface of every pixel, infinite where empty
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec4::ZERO; width * height],
+            depth: vec![f32::INFINITY; width * height],
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Pixels in row-major order starting from the top left corner
+    #[inline]
+    pub fn pixels(&self) -> &[Vec4] {
+        &self.pixels
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [Vec4] {
+        &mut self.pixels
+    }
+
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> Vec4 {
+        self.pixels[y * self.width + x]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, col: Vec4) {
+        self.pixels[y * self.width + x] = col;
+    }
+
+    #[inline]
+    pub fn depth(&self, x: usize, y: usize) -> f32 {
+        self.depth[y * self.width + x]
+    }
+
+    #[inline]
+    pub fn set_depth(&mut self, x: usize, y: usize, depth: f32) {
+        self.depth[y * self.width + x] = depth;
+    }
+
+    /// Depths in the same order as [`Framebuffer::pixels`]
+    #[inline]
+    pub fn depth_mut(&mut self) -> &mut [f32] {
+        &mut self.depth
+    }
+
+    /// Copies the pixels between `min` and `max`
+    pub fn crop(&self, min: [usize; 2], max: [usize; 2]) -> Framebuffer {
+        let (w, h) = (max[0] - min[0], max[1] - min[1]);
+        let mut img = Framebuffer::new(w, h);
+        for y in 0..h {
+            let row = (y + min[1]) * self.width + min[0];
+            img.pixels[y * w..(y + 1) * w].copy_from_slice(&self.pixels[row..row + w]);
+            img.depth[y * w..(y + 1) * w].copy_from_slice(&self.depth[row..row + w]);
+        }
+        img
+    }
+
+    /// Places frames of the same size side by side, `columns` across and row by row
+    pub fn tile(frames: &[Framebuffer], columns: usize) -> Framebuffer {
+        let Some(first) = frames.first() else {
+            return Framebuffer::default();
+        };
+        let (w, h) = (first.width, first.height);
+        let columns = columns.clamp(1, frames.len());
+        let rows = frames.len().div_ceil(columns);
+        let mut img = Framebuffer::new(w * columns, h * rows);
+        for (i, frame) in frames.iter().enumerate() {
+            let (x, y) = (i % columns * w, i / columns * h);
+            for row in 0..h.min(frame.height) {
+                let src = row * frame.width..row * frame.width + w.min(frame.width);
+                let dst = (y + row) * img.width + x;
+                img.pixels[dst..dst + src.len()].copy_from_slice(&frame.pixels[src.clone()]);
+                img.depth[dst..dst + src.len()].copy_from_slice(&frame.depth[src]);
+            }
+        }
+        img
+    }
+
+    /// Composites `layer` with the framebuffer, its top left corner at `min`.
+    /// Every pixel goes over or under the one already there depending on which is nearer.
+    pub fn blend(&mut self, layer: &Framebuffer, min: [usize; 2]) {
+        let w = layer.width.min(self.width.saturating_sub(min[0]));
+        let h = layer.height.min(self.height.saturating_sub(min[1]));
+        for y in 0..h {
+            for x in 0..w {
+                let (src, src_depth) = (layer.get(x, y), layer.depth(x, y));
+                let i = (y + min[1]) * self.width + x + min[0];
+                let (dst, dst_depth) = (self.pixels[i], self.depth[i]);
+                if src_depth <= dst_depth {
+                    self.pixels[i] = src + dst * (1.0 - src.w);
+                    if src.w > 0.0 {
+                        self.depth[i] = src_depth;
+                    }
+                } else {
+                    self.pixels[i] = dst + src * (1.0 - dst.w);
+                }
+            }
+        }
+    }
+
+    /// Tone maps the framebuffer for display
+    pub fn to_color_image(&self, tone_mapping: &ToneMapping) -> ColorImage {
+        use rayon::prelude::*;
+
+        let mut img = ColorImage::new([self.width, self.height], Color32::TRANSPARENT);
+        img.pixels
+            .par_iter_mut()
+            .zip(self.pixels.par_iter())
+            .for_each(|(pixel, &col)| *pixel = tone_mapping.apply(col));
+        img
+    }
+
+    /// Tone mapped, unmultiplied sRGB bytes, ready to be written to an image file
+    pub fn to_rgba8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        self.to_color_image(tone_mapping)
+            .pixels
+            .iter()
+            .flat_map(|p| p.to_srgba_unmultiplied())
+            .collect()
+    }
+
+    /// Exposed linear radiance without tone mapping, for HDR image files
+    pub fn to_linear_rgb(&self, tone_mapping: &ToneMapping) -> Vec<Vec3> {
+        let scale = tone_mapping.scale();
+        self.pixels.iter().map(|p| p.xyz() * scale).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let mut fb = Framebuffer::new(4, 3);
+        let mut background = Framebuffer::new(4, 3);
+        background.pixels_mut().fill(Vec4::new(2.0, 0.0, 0.0, 1.0));
+        fb.blend(&background, [0, 0]);
+
+        let mut layer = Framebuffer::new(8, 8);
+        layer.pixels_mut().fill(Vec4::new(0.0, 0.5, 0.0, 0.5));
+        fb.blend(&layer, [2, 1]);
+
+        assert_eq!(fb.get(0, 0), Vec4::new(2.0, 0.0, 0.0, 1.0));
+        assert_eq!(fb.get(3, 2), Vec4::new(1.0, 0.5, 0.0, 1.0));
+        assert_eq!(fb.to_rgba8(&ToneMapping::default()).len(), 4 * 3 * 4);
+        assert_eq!(fb.crop([2, 1], [4, 3]).get(1, 1), fb.get(3, 2));
+    }
+
+    #[test]
+    fn test_tile() {
+        let frames: Vec<_> = (0..3)
+            .map(|i| {
+                let mut frame = Framebuffer::new(2, 1);
+                frame.pixels_mut().fill(Vec4::splat(i as f32));
+                frame
+            })
+            .collect();
+        let img = Framebuffer::tile(&frames, 2);
+        assert_eq!((img.width(), img.height()), (4, 2));
+        assert_eq!(img.get(3, 0), Vec4::splat(1.0));
+        assert_eq!(img.get(1, 1), Vec4::splat(2.0));
+        assert_eq!(img.get(2, 1), Vec4::ZERO);
+    }
+
+    #[test]
+    fn test_blend_by_depth() {
+        let mut fb = Framebuffer::new(2, 1);
+        let mut terrain = Framebuffer::new(2, 1);
+        terrain.pixels_mut().fill(Vec4::new(0.0, 1.0, 0.0, 1.0));
+        terrain.depth_mut().fill(10.0);
+        fb.blend(&terrain, [0, 0]);
+
+        // Half transparent cloud in front of the terrain on the left and behind it on the right
+        let mut cloud = Framebuffer::new(2, 1);
+        cloud.pixels_mut().fill(Vec4::new(0.5, 0.0, 0.0, 0.5));
+        cloud.set_depth(0, 0, 5.0);
+        cloud.set_depth(1, 0, 20.0);
+        fb.blend(&cloud, [0, 0]);
+
+        assert_eq!(fb.get(0, 0), Vec4::new(0.5, 0.5, 0.0, 1.0));
+        assert_eq!(fb.depth(0, 0), 5.0);
+        assert_eq!(fb.get(1, 0), Vec4::new(0.0, 1.0, 0.0, 1.0));
+        assert_eq!(fb.depth(1, 0), 10.0);
+    }
+}
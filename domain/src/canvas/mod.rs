@@ -1,4 +1,10 @@
+pub mod color;
+pub mod framebuffer;
 pub mod painter;
+pub mod tone_mapping;
+
+pub use framebuffer::Framebuffer;
+pub use tone_mapping::{ToneMapOperator, ToneMapping};
 
 use egui::Stroke;
 use glam::Vec3;
@@ -41,6 +41,12 @@ impl Painter3D {
         self.resp_rect
     }
 
+    /// Painter for a part of the canvas, clipped to it
+    pub fn viewport(&self, rect: egui::Rect) -> Self {
+        let clip = self.painter_2d.clip_rect().intersect(rect);
+        Self::new(self.painter_2d.with_clip_rect(clip), rect, self.color)
+    }
+
     pub fn text(
         &self,
         pos: Vec3,
@@ -0,0 +1,126 @@
+use egui::Color32;
+use glam::{Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
+
+use super::color;
+use crate::object::camera::Camera;
+
+/// Curve that compresses HDR radiance into the displayable range
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToneMapOperator {
+    /// Clamps to `[0, 1]`, bright areas clip
+    Clamp,
+    Reinhard,
+    /// Narkowicz fit of the ACES reference rendering transform
+    #[default]
+    Aces,
+    /// Hable's filmic curve from Uncharted 2
+    Filmic,
+}
+
+impl ToneMapOperator {
+    pub const ALL: [ToneMapOperator; 4] = [Self::Clamp, Self::Reinhard, Self::Aces, Self::Filmic];
+
+    pub fn map(self, col: Vec3) -> Vec3 {
+        let col = col.max(Vec3::ZERO);
+        match self {
+            Self::Clamp => col,
+            Self::Reinhard => col / (col + 1.0),
+            Self::Aces => {
+                let x = col * 0.6;
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }
+            Self::Filmic => hable(col * 2.0) / hable(Vec3::splat(FILMIC_WHITE)),
+        }
+        .clamp(Vec3::ZERO, Vec3::ONE)
+    }
+}
+
+/// Linear radiance that maps to white with the filmic curve
+const FILMIC_WHITE: f32 = 11.2;
+
+#[inline]
+fn hable(x: Vec3) -> Vec3 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+/// Final stage turning linear HDR radiance into sRGB display colours
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    /// Exposure compensation in stops
+    pub exposure: f32,
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator, exposure: f32) -> Self {
+        Self { operator, exposure }
+    }
+
+    /// Exposure of the physical camera added on top of the compensation
+    pub fn for_camera(&self, camera: &Camera) -> Self {
+        Self {
+            exposure: self.exposure + camera.physical.exposure(),
+            ..*self
+        }
+    }
+
+    #[inline]
+    pub fn scale(&self) -> f32 {
+        self.exposure.exp2()
+    }
+
+    /// Maps a premultiplied linear colour to an sRGB encoded `Color32`.
+    /// Colours without coverage, such as stars, stay additive.
+    pub fn apply(&self, col: Vec4) -> Color32 {
+        let alpha = col.w.clamp(0.0, 1.0);
+        // Tone mapping works on the colour itself, not on its coverage
+        let rgb = match alpha > 0.0 {
+            true => self.operator.map(col.xyz() / alpha * self.scale()) * alpha,
+            false => self.operator.map(col.xyz() * self.scale()),
+        };
+        color::to_color32_premultiplied(rgb.extend(alpha))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operators_keep_range() {
+        for op in ToneMapOperator::ALL {
+            assert!(op.map(Vec3::ZERO).max_element() < 1e-3, "{op:?}");
+            let bright = op.map(Vec3::splat(100.0));
+            assert!(
+                bright.max_element() <= 1.0 && bright.min_element() > 0.9,
+                "{op:?}"
+            );
+            assert!(op.map(Vec3::splat(0.5)).x < bright.x, "{op:?}");
+        }
+    }
+
+    #[test]
+    fn test_exposure_and_srgb() {
+        let tm = ToneMapping::new(ToneMapOperator::Clamp, 0.0);
+        assert_eq!(
+            tm.apply(Vec4::new(0.5, 0.5, 0.5, 1.0)),
+            Color32::from_gray(188)
+        );
+
+        let brighter = ToneMapping::new(ToneMapOperator::Clamp, 1.0);
+        assert_eq!(
+            brighter.apply(Vec4::new(0.5, 0.5, 0.5, 1.0)),
+            Color32::WHITE
+        );
+        assert_eq!(tm.apply(Vec4::ZERO), Color32::TRANSPARENT);
+        assert_eq!(tm.apply(Vec4::new(1.0, 1.0, 1.0, 0.0)).a(), 0);
+    }
+}
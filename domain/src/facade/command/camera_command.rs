@@ -1,6 +1,26 @@
+use std::collections::BTreeMap;
+
+use glam::Vec3;
+use log::warn;
+
 use crate::facade::Command;
 use crate::managers::ManagerSolution;
-use crate::object::camera::Camera;
+use crate::object::camera::{Camera, ControllerKind, PhysicalCamera, ProjectionKind};
+
+pub enum CameraCommandReturn {
+    Nothing,
+    Bookmarks(BTreeMap<String, Camera>),
+}
+
+impl CameraCommandReturn {
+    #[inline]
+    pub fn into_bookmarks(self) -> Option<BTreeMap<String, Camera>> {
+        if let Self::Bookmarks(bookmarks) = self {
+            return Some(bookmarks);
+        }
+        None
+    }
+}
 
 #[derive(Debug)]
 pub enum CameraCommand {
@@ -8,12 +28,60 @@ pub enum CameraCommand {
     Zoom(f32),
     Pivot(f32, f32),
     SetCamera(Camera),
+    SetProjection(ProjectionKind),
+    SetController(ControllerKind),
+    /// Aperture, focus and shutter for depth of field, motion blur and exposure
+    SetPhysical(PhysicalCamera),
+    /// Direction along the right, up and forward axes and the elapsed seconds
+    Fly(Vec3, f32),
+    /// Replaces the bookmarks, e.g. with the ones stored in a scene description
+    SetBookmarks(BTreeMap<String, Camera>),
+    GetBookmarks,
+    SaveBookmark(String),
+    RemoveBookmark(String),
+    /// Moves smoothly to the bookmarked camera
+    RecallBookmark(String),
+    /// Moves smoothly to fit the bounding box of the object in view
+    FrameObject(&'static str),
+    /// Advances the smooth move by the elapsed seconds
+    Update(f32),
 }
 
 impl Command for CameraCommand {
-    type ReturnType = ();
-    fn exec(self, manager: &mut ManagerSolution) {
+    type ReturnType = CameraCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        if let CameraCommand::FrameObject(name) = self {
+            let bb = manager
+                .get_scene_manager()
+                .get_scene()
+                .get_object(name)
+                .and_then(|object| object.bounding_box());
+            if let Some(bb) = bb {
+                let cm = manager.get_mut_camera_manager();
+                let framed = cm.get_camera().framing(&bb);
+                cm.move_to(framed);
+            }
+            return CameraCommandReturn::Nothing;
+        }
+        if let CameraCommand::GetBookmarks = self {
+            let bookmarks = manager.get_camera_manager().bookmarks().clone();
+            return CameraCommandReturn::Bookmarks(bookmarks);
+        }
+
         let cm = manager.get_mut_camera_manager();
+        // Moving the camera by hand takes over from a smooth move
+        match self {
+            CameraCommand::Zoom(0.0) => {}
+            CameraCommand::Pan(..)
+            | CameraCommand::Zoom(_)
+            | CameraCommand::Pivot(..)
+            | CameraCommand::SetCamera(_)
+            | CameraCommand::SetProjection(_)
+            | CameraCommand::SetController(_)
+            | CameraCommand::SetPhysical(_)
+            | CameraCommand::Fly(..) => cm.cancel_transition(),
+            _ => {}
+        }
         let camera = cm.get_mut_camera();
         match self {
             CameraCommand::Pan(x, y) => {
@@ -28,6 +96,37 @@ impl Command for CameraCommand {
             CameraCommand::SetCamera(c) => {
                 cm.set_camera(c);
             }
+            CameraCommand::SetProjection(kind) => {
+                camera.kind = kind;
+            }
+            CameraCommand::SetController(controller) => {
+                camera.set_controller(controller);
+            }
+            CameraCommand::SetPhysical(physical) => {
+                camera.physical = physical;
+            }
+            CameraCommand::Fly(direction, dt) => {
+                camera.fly(direction, dt);
+            }
+            CameraCommand::SetBookmarks(bookmarks) => {
+                cm.set_bookmarks(bookmarks);
+            }
+            CameraCommand::SaveBookmark(name) => {
+                cm.save_bookmark(name);
+            }
+            CameraCommand::RemoveBookmark(name) => {
+                cm.remove_bookmark(&name);
+            }
+            CameraCommand::RecallBookmark(name) => {
+                if !cm.recall_bookmark(&name) {
+                    warn!("no camera bookmark {name}");
+                }
+            }
+            CameraCommand::FrameObject(_) | CameraCommand::GetBookmarks => {}
+            CameraCommand::Update(dt) => {
+                cm.update(dt);
+            }
         }
+        CameraCommandReturn::Nothing
     }
 }
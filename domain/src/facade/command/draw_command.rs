@@ -1,16 +1,59 @@
 use crate::canvas::painter::Painter3D;
+use crate::canvas::{Framebuffer, ToneMapOperator};
 use crate::facade::Command;
 use crate::managers::ManagerSolution;
+use crate::object::camera_rig::CameraRig;
+use crate::visitor::draw_visitor::{CloudDebugView, CloudRenderMode, TerrainDebug};
+use crate::visitor::sampling::Supersampling;
+use crate::visitor::stats::RenderStats;
+
+pub enum DrawCommandReturn {
+    Nothing,
+    Image(Framebuffer),
+    Stats(RenderStats),
+}
+
+impl DrawCommandReturn {
+    #[inline]
+    pub fn into_image(self) -> Option<Framebuffer> {
+        if let Self::Image(image) = self {
+            return Some(image);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn into_stats(self) -> Option<RenderStats> {
+        if let Self::Stats(stats) = self {
+            return Some(stats);
+        }
+        None
+    }
+}
 
 pub enum DrawCommand {
     SetPainter(Painter3D),
     SetPainterColor(egui::Color32),
+    SetToneMapOperator(ToneMapOperator),
+    /// Exposure compensation in stops
+    SetExposure(f32),
+    SetCloudRenderMode(CloudRenderMode),
+    SetSupersampling(Supersampling),
+    SetCloudDebugView(CloudDebugView),
+    SetTerrainDebug(TerrainDebug),
+    /// Views drawn or rendered together, e.g. a stereo pair
+    SetCameraRig(CameraRig),
     Draw,
+    /// Timings and counters of the last draw
+    GetStats,
+    /// Renders every view of the scene offscreen at the given width and height
+    /// and places them side by side
+    RenderImage(usize, usize),
 }
 
 impl Command for DrawCommand {
-    type ReturnType = ();
-    fn exec(self, manager: &mut ManagerSolution) {
+    type ReturnType = DrawCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
         match self {
             Self::SetPainter(painter) => {
                 let dm = manager.get_mut_draw_manager();
@@ -20,13 +63,54 @@ impl Command for DrawCommand {
                 let dm = manager.get_mut_draw_manager();
                 dm.set_color(color);
             }
+            Self::SetToneMapOperator(operator) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_tone_map_operator(operator);
+            }
+            Self::SetExposure(exposure) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_exposure(exposure);
+            }
+            Self::SetCloudRenderMode(mode) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_cloud_render_mode(mode);
+            }
+            Self::SetSupersampling(supersampling) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_supersampling(supersampling);
+            }
+            Self::SetCloudDebugView(view) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_cloud_debug_view(view);
+            }
+            Self::SetTerrainDebug(debug) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_terrain_debug(debug);
+            }
+            Self::SetCameraRig(rig) => {
+                let dm = manager.get_mut_draw_manager();
+                dm.set_camera_rig(rig);
+            }
             Self::Draw => {
+                let draw = &mut manager.draw_manager;
+                let camera = manager.camera_manager.get_camera();
+                let scene = manager.scene_manager.get_scene();
+
+                let views = draw.views(camera);
+                draw.draw_scene(scene, &views)
+            }
+            Self::GetStats => {
+                let dm = manager.get_draw_manager();
+                return DrawCommandReturn::Stats(dm.stats().clone());
+            }
+            Self::RenderImage(width, height) => {
                 let draw = manager.get_draw_manager();
                 let camera = manager.get_camera_manager().get_camera();
                 let scene = manager.get_scene_manager().get_scene();
 
-                draw.draw_scene(scene, camera)
+                return DrawCommandReturn::Image(draw.render_views(scene, camera, width, height));
             }
         }
+        DrawCommandReturn::Nothing
     }
 }
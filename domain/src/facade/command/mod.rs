@@ -3,8 +3,8 @@ mod draw_command;
 mod scene_command;
 
 use crate::managers::ManagerSolution;
-pub use camera_command::CameraCommand;
-pub use draw_command::DrawCommand;
+pub use camera_command::{CameraCommand, CameraCommandReturn};
+pub use draw_command::{DrawCommand, DrawCommandReturn};
 pub use scene_command::SceneCommand;
 
 pub trait Command: Sized + Send + Sync {
@@ -3,12 +3,15 @@ use log::debug;
 
 use crate::facade::Command;
 use crate::managers::ManagerSolution;
+use crate::math::TimeOfDay;
+use crate::object::objects::texture3d::NoiseBuilder;
+use crate::object::objects::textures::slice::{NoiseSlice, SliceImage};
 use crate::object::Component;
-use crate::object::objects::texture3d::{NoiseBuilder};
 
 pub enum SceneCommandReturn {
     Nothing,
     SunPos(glam::Vec3),
+    NoiseSlice(SliceImage),
 }
 impl SceneCommandReturn {
     #[inline]
@@ -18,6 +21,14 @@ impl SceneCommandReturn {
         }
         None
     }
+
+    #[inline]
+    pub fn into_noise_slice(self) -> Option<SliceImage> {
+        if let Self::NoiseSlice(slice) = self {
+            return Some(slice);
+        }
+        None
+    }
 }
 pub enum SceneCommand {
     AddObject(&'static str, Component),
@@ -30,6 +41,8 @@ pub enum SceneCommand {
     SetDensityThreshold(&'static str, f32),
     SetDensityOffset(&'static str, f32),
     SetOffset(&'static str, glam::Vec3),
+    /// Offset change per second, only used for motion blur
+    SetWind(&'static str, glam::Vec3),
     SetAlphaThreshold(&'static str, u8),
     MoveBoundingBox(&'static str, glam::Vec3),
     ExtendBoundingBox(&'static str, glam::Vec3),
@@ -46,7 +59,6 @@ pub enum SceneCommand {
     SetLightAbsorptionThroughCloud(&'static str, f32),
     SetDarknessThreshold(&'static str, f32),
     SetRayOffsetStrength(&'static str, f32),
-    SetLightColor(&'static str, Color32),
     SetColA(&'static str, Color32),
     SetColB(&'static str, Color32),
     SetHeightMapFactor(&'static str, f32),
@@ -54,7 +66,16 @@ pub enum SceneCommand {
     SetEdgeDistance(&'static str, f32),
     SetSunDistance(&'static str, f32),
     SetSunAngle(&'static str, glam::Vec2),
+    SetSunTimeOfDay(&'static str, TimeOfDay),
+    SetSunColor(&'static str, Color32),
+    SetSunIntensity(&'static str, f32),
+    SetSunAngularDiameter(&'static str, f32),
     GetSunPos(&'static str),
+    /// Samples a slice of one of the textures of a cloud
+    GetNoiseSlice(&'static str, NoiseSlice),
+    SetMoonAngle(&'static str, glam::Vec2),
+    SetMoonPhase(&'static str, f32),
+    SetMoonIntensity(&'static str, f32),
     SetTerrainScale(&'static str, usize),
     SetTerrainNoise(&'static str, NoiseBuilder),
     SetTerrainNoiseWeight(&'static str, glam::Vec4),
@@ -64,6 +85,10 @@ pub enum SceneCommand {
     SetTerrainNumShadowsSteps(&'static str, usize),
     SetTerrainDensityScale(&'static str, f32),
     SetTerrainDiffuseFactor(&'static str, f32),
+    SetSkyTurbidity(&'static str, f32),
+    SetSkyGroundAlbedo(&'static str, glam::Vec3),
+    SetSkyPlanetRadius(&'static str, f32),
+    SetSkyAerialStrength(&'static str, f32),
 }
 impl Command for SceneCommand {
     type ReturnType = SceneCommandReturn;
@@ -136,6 +161,13 @@ impl Command for SceneCommand {
                     }
                 }
             }
+            SceneCommand::SetWind(id, wind) => {
+                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                    if let Component::Cloud(cloud) = i {
+                        cloud.wind = wind
+                    }
+                }
+            }
             SceneCommand::SetAlphaThreshold(id, threshold) => {
                 if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
@@ -227,13 +259,6 @@ impl Command for SceneCommand {
                     }
                 }
             }
-            SceneCommand::SetLightColor(id, light_color) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
-                    if let Component::Cloud(cloud) = i {
-                        cloud.light_color = light_color
-                    }
-                }
-            }
             SceneCommand::SetColA(id, col_a) => {
                 if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
@@ -283,6 +308,34 @@ impl Command for SceneCommand {
                     sun.prepend_angle(a);
                 }
             }
+            SceneCommand::SetSunTimeOfDay(id, time) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sun.set_time_of_day(&time);
+                }
+            }
+            SceneCommand::SetSunColor(id, color) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sun.set_color(color);
+                }
+            }
+            SceneCommand::SetSunIntensity(id, intensity) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sun.set_intensity(intensity);
+                }
+            }
+            SceneCommand::SetSunAngularDiameter(id, angular_diameter) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sun.set_angular_diameter(angular_diameter);
+                }
+            }
             SceneCommand::GetSunPos(id) => {
                 if let Some(Component::Sun(sun)) =
                     manager.get_mut_scene_manager().get_mut_object(id)
@@ -291,6 +344,35 @@ impl Command for SceneCommand {
                     return SceneCommandReturn::SunPos(sun_pos);
                 }
             }
+            SceneCommand::GetNoiseSlice(id, slice) => {
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_scene_manager().get_scene().get_object(id)
+                {
+                    let noise = cloud.texture(slice.texture);
+                    return SceneCommandReturn::NoiseSlice(slice.sample(noise));
+                }
+            }
+            SceneCommand::SetMoonAngle(id, a) => {
+                if let Some(Component::Moon(moon)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    moon.set_angle(a.x, a.y);
+                }
+            }
+            SceneCommand::SetMoonPhase(id, phase) => {
+                if let Some(Component::Moon(moon)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    moon.set_phase(phase);
+                }
+            }
+            SceneCommand::SetMoonIntensity(id, intensity) => {
+                if let Some(Component::Moon(moon)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    moon.set_intensity(intensity);
+                }
+            }
             SceneCommand::SetTerrainScale(id, s) => {
                 if let Some(Component::Terrain(terrain)) =
                     manager.get_mut_scene_manager().get_mut_object(id)
@@ -356,6 +438,34 @@ impl Command for SceneCommand {
                     terrain.noise_weight = noise_weight;
                 }
             }
+            SceneCommand::SetSkyTurbidity(id, turbidity) => {
+                if let Some(Component::Sky(sky)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sky.turbidity = turbidity;
+                }
+            }
+            SceneCommand::SetSkyGroundAlbedo(id, ground_albedo) => {
+                if let Some(Component::Sky(sky)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sky.ground_albedo = ground_albedo;
+                }
+            }
+            SceneCommand::SetSkyPlanetRadius(id, planet_radius) => {
+                if let Some(Component::Sky(sky)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sky.planet_radius = planet_radius;
+                }
+            }
+            SceneCommand::SetSkyAerialStrength(id, aerial_strength) => {
+                if let Some(Component::Sky(sky)) =
+                    manager.get_mut_scene_manager().get_mut_object(id)
+                {
+                    sky.aerial_strength = aerial_strength;
+                }
+            }
             SceneCommand::MoveBoundingBox(id, bb) => {
                 if let Some(Component::Cloud(cloud)) =
                     manager.get_mut_scene_manager().get_mut_object(id)
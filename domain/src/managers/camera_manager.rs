@@ -1,9 +1,17 @@
+use std::collections::BTreeMap;
+
 use crate::managers::Manager;
 use crate::object::camera::Camera;
+use crate::object::camera_path::CameraTransition;
+
+/// Seconds the camera takes to move to a bookmark or a framed object
+const TRANSITION_TIME: f32 = 1.0;
 
 #[derive(Default, Debug)]
 pub struct CameraManager {
     camera: Camera,
+    bookmarks: BTreeMap<String, Camera>,
+    transition: Option<CameraTransition>,
 }
 
 impl CameraManager {
@@ -18,6 +26,83 @@ impl CameraManager {
     pub fn get_mut_camera(&mut self) -> &mut Camera {
         &mut self.camera
     }
+
+    pub fn bookmarks(&self) -> &BTreeMap<String, Camera> {
+        &self.bookmarks
+    }
+
+    pub fn set_bookmarks(&mut self, bookmarks: BTreeMap<String, Camera>) {
+        self.bookmarks = bookmarks;
+    }
+
+    /// Remembers the current camera under `name`, replacing the old bookmark
+    pub fn save_bookmark(&mut self, name: String) {
+        self.bookmarks.insert(name, self.camera);
+    }
+
+    pub fn remove_bookmark(&mut self, name: &str) {
+        self.bookmarks.remove(name);
+    }
+
+    /// Starts moving to the bookmark, `false` when there is no such bookmark
+    pub fn recall_bookmark(&mut self, name: &str) -> bool {
+        let Some(&camera) = self.bookmarks.get(name) else {
+            return false;
+        };
+        self.move_to(camera);
+        true
+    }
+
+    /// Starts a smooth move from the current camera to `camera`,
+    /// the lens settings stay the current ones
+    pub fn move_to(&mut self, mut camera: Camera) {
+        camera.physical = self.camera.physical;
+        self.transition = Some(CameraTransition::new(&self.camera, camera, TRANSITION_TIME));
+    }
+
+    /// Stops the move in progress, leaving the camera where it is
+    pub fn cancel_transition(&mut self) {
+        self.transition = None;
+    }
+
+    /// Advances the move in progress by `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+        self.camera = transition.advance(dt);
+        if transition.finished() {
+            self.transition = None;
+        }
+    }
 }
 
 impl Manager for CameraManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::camera::ControllerKind;
+
+    #[test]
+    fn test_cancelled_move_keeps_the_target_controller() {
+        let mut manager = CameraManager::default();
+        manager.get_mut_camera().set_controller(ControllerKind::Fly);
+        let mut to = Camera::default();
+        to.view.yaw += 1.0;
+        manager.move_to(to);
+
+        manager.update(TRANSITION_TIME * 0.5);
+        manager.cancel_transition();
+        let camera = *manager.get_camera();
+        assert_eq!(camera.controller, ControllerKind::ArcBall);
+        assert!(camera.pos().distance(to.pos()) > 0.1);
+
+        // The arcball orbits around a pivot in front of the camera, where the move left it
+        let mut orbited = camera;
+        orbited.pivot(10.0, 0.0);
+        assert!(!orbited.dir().abs_diff_eq(camera.dir(), 1e-3));
+        let pivot = camera.pos() + camera.dir() * camera.view.distance;
+        assert!(orbited.view.pivot.abs_diff_eq(pivot, 1e-3));
+    }
+}
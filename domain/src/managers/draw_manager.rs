@@ -1,17 +1,40 @@
+use std::time::Duration;
+
 use egui::{Color32, Stroke};
 
 use crate::canvas::painter::Painter3D;
+use crate::canvas::{Framebuffer, ToneMapOperator, ToneMapping};
 use crate::managers::Manager;
 use crate::object::camera::Camera;
+use crate::object::camera_rig::CameraRig;
 use crate::scene::scene::Scene;
-use crate::visitor::draw_visitor::DrawVisitor;
+use crate::visitor::draw_visitor::{CloudDebugView, CloudRenderMode, DrawVisitor, TerrainDebug};
+use crate::visitor::image_renderer::ImageRenderer;
+use crate::visitor::progressive::ProgressiveCloud;
+use crate::visitor::sampling::Supersampling;
+use crate::visitor::stats::RenderStats;
+use crate::visitor::temporal::TemporalCloud;
 use crate::visitor::Visitable;
 
+/// Time spent on clouds per draw when they are rendered progressively
+const CLOUD_BUDGET: Duration = Duration::from_millis(25);
+
 #[derive(Default)]
 pub struct DrawManager {
     canvas: Option<Painter3D>,
     stroke: Stroke,
     color: Color32,
+    tone_mapping: ToneMapping,
+    cloud_mode: CloudRenderMode,
+    supersampling: Supersampling,
+    cloud_debug: CloudDebugView,
+    terrain_debug: TerrainDebug,
+    rig: CameraRig,
+    /// Cloud frames of every view kept across draws by the progressive and the temporal modes
+    progressive: Vec<Vec<ProgressiveCloud>>,
+    temporal: Vec<Vec<TemporalCloud>>,
+    /// Of the last draw, summed over the views
+    stats: RenderStats,
 }
 
 impl DrawManager {
@@ -27,12 +50,146 @@ impl DrawManager {
         self.color = color;
     }
 
-    pub fn draw_scene(&self, scene: &Scene, camera: &Camera) {
-        if let Some(canvas) = &self.canvas {
-            let mut visitor = DrawVisitor::new(camera, canvas).with_stroke(self.stroke);
+    pub fn set_tone_map_operator(&mut self, operator: ToneMapOperator) {
+        self.tone_mapping.operator = operator;
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.tone_mapping.exposure = exposure;
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn set_cloud_render_mode(&mut self, mode: CloudRenderMode) {
+        self.cloud_mode = mode;
+        self.progressive.clear();
+        self.temporal.clear();
+    }
+
+    /// Cached cloud frames are dropped, they were rendered with the old samples
+    pub fn set_supersampling(&mut self, supersampling: Supersampling) {
+        self.supersampling = supersampling;
+        self.progressive.clear();
+        self.temporal.clear();
+    }
+
+    pub fn supersampling(&self) -> Supersampling {
+        self.supersampling
+    }
+
+    pub fn set_cloud_debug_view(&mut self, view: CloudDebugView) {
+        self.cloud_debug = view;
+    }
+
+    pub fn set_terrain_debug(&mut self, debug: TerrainDebug) {
+        self.terrain_debug = debug;
+    }
+
+    /// Cached cloud frames are dropped, the views they belong to changed
+    pub fn set_camera_rig(&mut self, rig: CameraRig) {
+        self.rig = rig;
+        self.progressive.clear();
+        self.temporal.clear();
+    }
+
+    pub fn camera_rig(&self) -> CameraRig {
+        self.rig
+    }
+
+    /// Fraction of the progressive cloud work done for the current views,
+    /// `1.0` when nothing is pending
+    pub fn cloud_progress(&self) -> f32 {
+        self.progressive
+            .iter()
+            .flatten()
+            .map(ProgressiveCloud::progress)
+            .fold(1.0, f32::min)
+    }
+
+    /// Timings and counters of the last draw, summed over the views
+    pub fn stats(&self) -> &RenderStats {
+        &self.stats
+    }
+
+    /// Cameras of the rig around `camera`, each with its part of the canvas
+    pub fn views(&self, camera: &Camera) -> Vec<(Camera, Painter3D)> {
+        let Some(canvas) = &self.canvas else {
+            return Vec::new();
+        };
+        let [columns, rows] = self.rig.layout();
+        let rect = canvas.resp_rect();
+        let size = egui::vec2(rect.width() / columns as f32, rect.height() / rows as f32);
+        self.rig
+            .cameras(camera)
+            .into_iter()
+            .enumerate()
+            .map(|(i, view)| {
+                let min = rect.min
+                    + egui::vec2((i % columns) as f32 * size.x, (i / columns) as f32 * size.y);
+                (view, canvas.viewport(egui::Rect::from_min_size(min, size)))
+            })
+            .collect()
+    }
+
+    /// Draws every view of the scene onto its painter, the scene and its noise textures
+    /// are shared between them
+    pub fn draw_scene(&mut self, scene: &Scene, views: &[(Camera, Painter3D)]) {
+        self.progressive.resize_with(views.len(), Vec::new);
+        self.temporal.resize_with(views.len(), Vec::new);
+        let mut stats = RenderStats::default();
+        let caches = self.progressive.iter_mut().zip(self.temporal.iter_mut());
+        for ((camera, canvas), (progressive, temporal)) in views.iter().zip(caches) {
+            let mut visitor = DrawVisitor::new(camera, canvas)
+                .with_stroke(self.stroke)
+                .with_tone_mapping(self.tone_mapping.for_camera(camera))
+                .with_supersampling(self.supersampling)
+                .with_cloud_debug(self.cloud_debug)
+                .with_terrain_debug(self.terrain_debug);
+            visitor = match self.cloud_mode {
+                CloudRenderMode::Full => visitor,
+                CloudRenderMode::Progressive => {
+                    visitor.with_progressive_clouds(progressive, CLOUD_BUDGET / views.len() as u32)
+                }
+                CloudRenderMode::Temporal => visitor.with_temporal_clouds(temporal),
+            };
 
             scene.accept(&mut visitor);
+            stats += visitor.stats();
         }
+        self.stats = stats;
+    }
+
+    /// Renders the scene offscreen into a linear HDR framebuffer, works without a canvas
+    pub fn render_image(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+    ) -> Framebuffer {
+        let mut renderer =
+            ImageRenderer::new(camera, width, height).with_supersampling(self.supersampling);
+        scene.accept(&mut renderer);
+        renderer.into_framebuffer()
+    }
+
+    /// Renders every view of the rig at `width` x `height` and places them side by side
+    pub fn render_views(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+    ) -> Framebuffer {
+        let frames: Vec<_> = self
+            .rig
+            .cameras(camera)
+            .iter()
+            .map(|view| self.render_image(scene, view, width, height))
+            .collect();
+        Framebuffer::tile(&frames, self.rig.layout()[0])
     }
 }
 
@@ -1,2 +1,4 @@
+pub mod solar;
 pub mod transform;
+pub use solar::{SolarPosition, TimeOfDay};
 pub use transform::Transform;
@@ -0,0 +1,299 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+const DAYS_IN_MONTH: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+/// Sun position in the local horizontal frame, degrees
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SolarPosition {
+    /// Angle above the horizon
+    pub elevation: f32,
+    /// Angle from north, clockwise through east
+    pub azimuth: f32,
+}
+
+/// Date, local time and place on Earth used to compute the sun position
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "TimeOfDayFields")]
+pub struct TimeOfDay {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    /// Local time in hours, `0.0..24.0`
+    pub hours: f32,
+    /// Offset of the local time from UTC in hours
+    pub utc_offset: f32,
+    /// Degrees, north is positive
+    pub latitude: f32,
+    /// Degrees, east is positive
+    pub longitude: f32,
+}
+
+/// [`TimeOfDay`] as read from a file, checked before use
+#[derive(Deserialize)]
+#[serde(default)]
+struct TimeOfDayFields {
+    year: i32,
+    month: u32,
+    day: u32,
+    hours: f32,
+    utc_offset: f32,
+    latitude: f32,
+    longitude: f32,
+}
+
+impl Default for TimeOfDayFields {
+    fn default() -> Self {
+        let t = TimeOfDay::default();
+        Self {
+            year: t.year,
+            month: t.month,
+            day: t.day,
+            hours: t.hours,
+            utc_offset: t.utc_offset,
+            latitude: t.latitude,
+            longitude: t.longitude,
+        }
+    }
+}
+
+impl TryFrom<TimeOfDayFields> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(f: TimeOfDayFields) -> Result<Self, Self::Error> {
+        if !(1..=12).contains(&f.month) {
+            return Err(format!("month {} is not in 1..=12", f.month));
+        }
+        let days = days_in_month(f.year, f.month);
+        if !(1..=days).contains(&f.day) {
+            return Err(format!(
+                "day {} is not in 1..={days} for month {}",
+                f.day, f.month
+            ));
+        }
+        if !(f.hours.is_finite() && f.latitude.is_finite()) {
+            return Err("hours and latitude must be finite".into());
+        }
+        // Wrapped and clamped the same way as by the builders
+        let time = Self {
+            year: f.year,
+            month: f.month,
+            day: f.day,
+            utc_offset: f.utc_offset,
+            ..Self::default()
+        };
+        Ok(time
+            .with_hours(f.hours)
+            .with_location(f.latitude, f.longitude))
+    }
+}
+
+// Moscow, summer solstice noon
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            year: 2024,
+            month: 6,
+            day: 21,
+            hours: 12.0,
+            utc_offset: 3.0,
+            latitude: 55.75,
+            longitude: 37.62,
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_date(mut self, year: i32, month: u32, day: u32) -> Self {
+        self.year = year;
+        self.month = month.clamp(1, 12);
+        self.day = day.clamp(1, days_in_month(year, self.month));
+        self
+    }
+
+    pub fn with_hours(mut self, hours: f32) -> Self {
+        self.hours = hours.rem_euclid(24.0);
+        self
+    }
+
+    pub fn with_utc_offset(mut self, utc_offset: f32) -> Self {
+        self.utc_offset = utc_offset;
+        self
+    }
+
+    pub fn with_location(mut self, latitude: f32, longitude: f32) -> Self {
+        self.latitude = latitude.clamp(-90.0, 90.0);
+        self.longitude = longitude;
+        self
+    }
+
+    /// Day of the year starting from 1
+    pub fn day_of_year(&self) -> u32 {
+        (1..self.month)
+            .map(|m| days_in_month(self.year, m))
+            .sum::<u32>()
+            + self.day
+    }
+
+    fn days_in_year(&self) -> f32 {
+        if is_leap_year(self.year) {
+            366.0
+        } else {
+            365.0
+        }
+    }
+
+    /// Moves the clock forward by the given number of hours, rolling over days, months and years
+    pub fn advance(&mut self, hours: f32) {
+        let total = self.hours + hours;
+        let mut days = total.div_euclid(24.0) as i64;
+        self.hours = total.rem_euclid(24.0);
+
+        while days > 0 {
+            self.day += 1;
+            if self.day > days_in_month(self.year, self.month) {
+                self.day = 1;
+                self.month += 1;
+                if self.month > 12 {
+                    self.month = 1;
+                    self.year += 1;
+                }
+            }
+            days -= 1;
+        }
+        while days < 0 {
+            if self.day > 1 {
+                self.day -= 1;
+            } else {
+                if self.month > 1 {
+                    self.month -= 1;
+                } else {
+                    self.month = 12;
+                    self.year -= 1;
+                }
+                self.day = days_in_month(self.year, self.month);
+            }
+            days += 1;
+        }
+    }
+
+    /// NOAA general solar position approximation, accurate to a fraction of a degree
+    pub fn solar_position(&self) -> SolarPosition {
+        let gamma = 2.0 * PI / self.days_in_year()
+            * (self.day_of_year() as f32 - 1.0 + (self.hours - 12.0) / 24.0);
+
+        let eq_time = 229.18
+            * (0.000075 + 0.001868 * gamma.cos()
+                - 0.032077 * gamma.sin()
+                - 0.014615 * (2.0 * gamma).cos()
+                - 0.040849 * (2.0 * gamma).sin());
+        let decl = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+            - 0.006758 * (2.0 * gamma).cos()
+            + 0.000907 * (2.0 * gamma).sin()
+            - 0.002697 * (3.0 * gamma).cos()
+            + 0.00148 * (3.0 * gamma).sin();
+
+        let time_offset = eq_time + 4.0 * self.longitude - 60.0 * self.utc_offset;
+        let true_solar_time = self.hours * 60.0 + time_offset;
+        let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+        let lat = self.latitude.to_radians();
+        let cos_zenith = lat.sin() * decl.sin() + lat.cos() * decl.cos() * hour_angle.cos();
+        let zenith = cos_zenith.clamp(-1.0, 1.0).acos();
+
+        let azimuth = hour_angle
+            .sin()
+            .atan2(hour_angle.cos() * lat.sin() - decl.tan() * lat.cos())
+            .to_degrees()
+            + 180.0;
+
+        SolarPosition {
+            elevation: 90.0 - zenith.to_degrees(),
+            azimuth: azimuth.rem_euclid(360.0),
+        }
+    }
+}
+
+#[inline]
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+#[inline]
+fn days_in_month(year: i32, month: u32) -> u32 {
+    if month == 2 && is_leap_year(year) {
+        29
+    } else {
+        // Months out of range only come from the public fields and count as the nearest one
+        DAYS_IN_MONTH[(month.clamp(1, 12) - 1) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_date_is_rejected() {
+        let parse = |json: &str| serde_json::from_str::<TimeOfDay>(json);
+        assert_eq!(
+            parse(r#"{"hours": 6.0}"#).unwrap(),
+            TimeOfDay::new().with_hours(6.0)
+        );
+        assert!(parse(r#"{"month": 0}"#).is_err());
+        assert!(parse(r#"{"month": 13}"#).is_err());
+        assert!(parse(r#"{"year": 2023, "month": 2, "day": 29}"#).is_err());
+        assert!(parse(r#"{"year": 2024, "month": 2, "day": 29}"#).is_ok());
+
+        let wrapped = parse(r#"{"hours": 25.0, "latitude": 100.0}"#).unwrap();
+        assert_eq!((wrapped.hours, wrapped.latitude), (1.0, 90.0));
+
+        let mut t = TimeOfDay {
+            month: 13,
+            ..TimeOfDay::new()
+        };
+        t.advance(24.0);
+        assert_eq!(days_in_month(2024, 0), 31);
+    }
+
+    #[test]
+    fn test_solar_noon_at_equinox() {
+        let pos = TimeOfDay::new()
+            .with_date(2024, 3, 20)
+            .with_hours(12.0)
+            .with_utc_offset(0.0)
+            .with_location(0.0, 0.0)
+            .solar_position();
+        assert!(pos.elevation > 85.0, "{:?}", pos);
+    }
+
+    #[test]
+    fn test_moscow_summer() {
+        let noon = TimeOfDay::new().with_hours(12.5).solar_position();
+        assert!((noon.elevation - 57.7).abs() < 1.0, "{:?}", noon);
+        assert!((noon.azimuth - 180.0).abs() < 10.0, "{:?}", noon);
+
+        let morning = TimeOfDay::new().with_hours(6.0).solar_position();
+        assert!(morning.azimuth < 90.0, "{:?}", morning);
+
+        let night = TimeOfDay::new().with_hours(0.5).solar_position();
+        assert!(night.elevation < 0.0, "{:?}", night);
+    }
+
+    #[test]
+    fn test_advance_rolls_over_year() {
+        let mut t = TimeOfDay::new().with_date(2023, 12, 31).with_hours(23.0);
+        t.advance(2.0);
+        assert_eq!((t.year, t.month, t.day), (2024, 1, 1));
+        assert!((t.hours - 1.0).abs() < 1e-4);
+
+        t.advance(-2.0);
+        assert_eq!((t.year, t.month, t.day), (2023, 12, 31));
+        assert_eq!(TimeOfDay::new().with_date(2024, 3, 1).day_of_year(), 61);
+    }
+}
@@ -1,17 +1,75 @@
-use std::f32::consts::FRAC_PI_2;
+use std::f32::consts::{FRAC_PI_2, PI};
 
-use egui::{Pos2, Rect, Vec2};
-use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use egui::Pos2;
+use glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
 
-use crate::math::Transform;
+use crate::object::objects::BoundingBox;
 use crate::visitor::{Visitable, Visitor};
 
 /// Camera controller and parameters
-#[derive(Default, Copy, Clone, Debug, PartialEq)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Camera {
+    pub kind: ProjectionKind,
     pub proj: Perspective,
+    pub ortho: Orthographic,
+    pub equirect: Equirectangular,
     pub view: ArcBall,
     pub control: ArcBallController,
+    pub controller: ControllerKind,
+    pub fly: Fly,
+    pub fly_control: FlyController,
+    pub physical: PhysicalCamera,
+}
+
+/// Maps the camera space, looking down `-z` with `y` up, to normalised device coordinates,
+/// `x` to the right and `y` up in `[-1, 1]`
+pub trait Projection {
+    /// Matrix of the projection for the vector overlays. Exact when the projection keeps
+    /// straight lines straight, an approximation of the central view otherwise.
+    fn matrix(&self, aspect: f32) -> Mat4;
+
+    /// Origin and normalised direction in camera space of the ray through `ndc`
+    fn ray(&self, ndc: Vec2, aspect: f32) -> (Vec3, Vec3);
+
+    /// Normalised device coordinates of a point in camera space,
+    /// `None` when it is outside the clipping range
+    fn project(&self, point: Vec3, aspect: f32) -> Option<Vec2>;
+
+    /// Whether straight lines stay straight, so that the projected corners of a box bound it
+    fn preserves_lines(&self) -> bool {
+        true
+    }
+}
+
+/// Projection used by the camera
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectionKind {
+    #[default]
+    Perspective,
+    Orthographic,
+    /// 360° panorama for sky environment maps
+    Equirectangular,
+}
+
+impl ProjectionKind {
+    pub const ALL: [ProjectionKind; 3] =
+        [Self::Perspective, Self::Orthographic, Self::Equirectangular];
+}
+
+/// How the camera is moved
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerKind {
+    /// Orbits around a pivot
+    #[default]
+    ArcBall,
+    /// Flies freely, for going inside and under the clouds
+    Fly,
+}
+
+impl ControllerKind {
+    pub const ALL: [ControllerKind; 2] = [Self::ArcBall, Self::Fly];
 }
 
 impl Visitable for Camera {
@@ -22,58 +80,225 @@ impl Visitable for Camera {
 
 impl Camera {
     pub fn pos(&self) -> Vec3 {
-        self.view.eye()
+        match self.controller {
+            ControllerKind::ArcBall => self.view.eye(),
+            ControllerKind::Fly => self.fly.pos,
+        }
     }
 
     pub fn dir(&self) -> Vec3 {
-        (self.view.pivot - self.view.eye()).normalize()
+        match self.controller {
+            ControllerKind::ArcBall => (self.view.pivot - self.view.eye()).normalize(),
+            ControllerKind::Fly => self.fly.dir(),
+        }
+    }
+
+    /// Return the projection in use
+    pub fn projector(&self) -> &dyn Projection {
+        match self.kind {
+            ProjectionKind::Perspective => &self.proj,
+            ProjectionKind::Orthographic => &self.ortho,
+            ProjectionKind::Equirectangular => &self.equirect,
+        }
     }
 
     /// Return the projection matrix of this camera
     pub fn projection(&self, width: f32, height: f32) -> Mat4 {
-        self.proj.matrix(width, height)
+        self.projector().matrix(width / height)
     }
 
     /// Return the view matrix of this camera
     pub fn view(&self) -> Mat4 {
-        self.view.matrix()
+        match self.controller {
+            ControllerKind::ArcBall => self.view.matrix(),
+            ControllerKind::Fly => self.fly.matrix(),
+        }
     }
 
-    /// Pivot the camera by the given mouse pointer delta
+    /// Pivot the camera by the given mouse pointer delta, the fly camera looks around
     pub fn pivot(&mut self, delta_x: f32, delta_y: f32) {
-        self.control.pivot(&mut self.view, delta_x, delta_y)
+        match self.controller {
+            ControllerKind::ArcBall => self.control.pivot(&mut self.view, delta_x, delta_y),
+            ControllerKind::Fly => self.fly_control.look(&mut self.fly, delta_x, delta_y),
+        }
     }
 
-    /// Pan the camera by the given mouse pointer delta
+    /// Pan the camera by the given mouse pointer delta, the fly camera strafes
     pub fn pan(&mut self, delta_x: f32, delta_y: f32) {
-        self.control.pan(&mut self.view, delta_x, delta_y)
+        match self.controller {
+            ControllerKind::ArcBall => self.control.pan(&mut self.view, delta_x, delta_y),
+            ControllerKind::Fly => self.fly_control.pan(&mut self.fly, delta_x, delta_y),
+        }
     }
 
-    /// Zoom the camera by the given mouse scroll delta
+    /// Moves the camera without turning it, the arcball carries its pivot along
+    pub fn translate(&mut self, delta: Vec3) {
+        match self.controller {
+            ControllerKind::ArcBall => self.view.pivot += delta,
+            ControllerKind::Fly => self.fly.pos += delta,
+        }
+    }
+
+    /// Zoom the camera by the given mouse scroll delta, the fly camera changes its speed.
+    /// The orthographic view shrinks along with the distance to the pivot.
     pub fn zoom(&mut self, delta: f32) {
-        self.control.zoom(&mut self.view, delta)
+        match self.controller {
+            ControllerKind::ArcBall => {
+                let distance = self.view.distance;
+                self.control.zoom(&mut self.view, delta);
+                self.ortho.height *= self.view.distance / distance;
+            }
+            ControllerKind::Fly => self.fly_control.accelerate(delta),
+        }
     }
 
-    pub fn egui_to_world(&self, i: usize, j: usize, width: usize, height: usize) -> Vec3 {
-        let t = Transform::new(
-            self.projection(width as f32, height as f32) * self.view(),
-            Rect::from_min_size(Pos2::ZERO, (width as f32, height as f32).into()),
-        );
+    /// Move the fly camera along its right, up and forward axes for `dt` seconds
+    pub fn fly(&mut self, direction: Vec3, dt: f32) {
+        if self.controller == ControllerKind::Fly {
+            self.fly_control.fly(&mut self.fly, direction, dt)
+        }
+    }
+
+    /// Switch the controller keeping the camera where it is. The arcball keeps
+    /// its distance and moves the pivot in front of the camera.
+    pub fn set_controller(&mut self, controller: ControllerKind) {
+        if controller == self.controller {
+            return;
+        }
+        let (pos, dir) = (self.pos(), self.dir());
+        match controller {
+            ControllerKind::ArcBall => {
+                self.view.face(dir);
+                self.view.pivot = pos + dir * self.view.distance;
+            }
+            ControllerKind::Fly => self.fly = Fly::looking(pos, dir),
+        }
+        self.controller = controller;
+    }
 
-        t.egui_to_world(Vec2::new(j as f32, i as f32), -1.)
+    /// Arcball camera looking the same way at the centre of the box,
+    /// close enough for the whole box to fit in view
+    pub fn framing(&self, bb: &BoundingBox) -> Camera {
+        let radius = (bb.size().length() * 0.5).max(self.control.closest_zoom);
+        let mut camera = *self;
+        camera.controller = ControllerKind::ArcBall;
+        camera.view.face(self.dir());
+        camera.view.pivot = bb.center();
+        camera.view.distance = match self.kind {
+            ProjectionKind::Perspective => radius / (self.proj.fov * 0.5).sin(),
+            ProjectionKind::Orthographic | ProjectionKind::Equirectangular => radius * 2.0,
+        };
+        camera.ortho.height = radius * 2.0;
+        camera
+    }
+
+    /// Right, up and forward axes of the camera in world space
+    pub fn basis(&self) -> [Vec3; 3] {
+        let forward = self.dir();
+        // A camera set up by hand may still look straight up or down
+        let up = match forward.y.abs() > 0.999 {
+            true => Vec3::NEG_Z,
+            false => Vec3::Y,
+        };
+        let right = forward.cross(up).normalize();
+        [right, right.cross(forward), forward]
+    }
+
+    /// Origin and normalised direction of the world ray through the point `x`, `y`
+    /// in pixels of a `width` x `height` frame
+    pub fn ray(&self, x: f32, y: f32, width: f32, height: f32) -> (Vec3, Vec3) {
+        let ndc = Vec2::new(x / width * 2.0 - 1.0, 1.0 - y / height * 2.0);
+        let (origin, dir) = self.projector().ray(ndc, width / height);
+        let [right, up, forward] = self.basis();
+        let to_world = |v: Vec3| right * v.x + up * v.y - forward * v.z;
+        (self.pos() + to_world(origin), to_world(dir))
+    }
+
+    /// Ray through the point `x`, `y` like [`Camera::ray`] that passes through the lens at
+    /// `lens` in `[0, 1)²` and leaves it towards the point in focus. The second value is the
+    /// time of the ray in seconds relative to the middle of the shutter interval, `shutter`
+    /// in `[0, 1)` picks it.
+    pub fn lens_ray(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        lens: Vec2,
+        shutter: f32,
+    ) -> ((Vec3, Vec3), f32) {
+        let (origin, dir) = self.ray(x, y, width, height);
+        let time = match self.physical.enabled {
+            true => (shutter - 0.5) * self.physical.shutter,
+            false => 0.0,
+        };
+        let radius = self.physical.lens_radius();
+        if radius <= 0.0 {
+            return ((origin, dir), time);
+        }
+        let [right, up, forward] = self.basis();
+        // The panorama focuses on a sphere, the others on a plane
+        let focus = match self.kind {
+            ProjectionKind::Equirectangular => self.physical.focus_distance,
+            _ => self.physical.focus_distance / dir.dot(forward),
+        };
+        let target = origin + dir * focus;
+        let disk = concentric_disk(lens) * radius;
+        let origin = origin + right * disk.x + up * disk.y;
+        ((origin, (target - origin).normalize()), time)
+    }
+
+    /// Position in pixels of a world point on a `width` x `height` frame,
+    /// `None` when it is outside the clipping range
+    pub fn project(&self, point: Vec3, width: f32, height: f32) -> Option<Pos2> {
+        let [right, up, forward] = self.basis();
+        let v = point - self.pos();
+        let local = Vec3::new(v.dot(right), v.dot(up), -v.dot(forward));
+        let ndc = self.projector().project(local, width / height)?;
+        Some(Pos2::new(
+            (ndc.x + 1.0) * 0.5 * width,
+            (1.0 - ndc.y) * 0.5 * height,
+        ))
+    }
+
+    /// Point of the ray through the pixel in row `i` and column `j` one unit away from its origin
+    pub fn egui_to_world(&self, i: usize, j: usize, width: usize, height: usize) -> Vec3 {
+        let (origin, dir) = self.ray(j as f32, i as f32, width as f32, height as f32);
+        origin + dir
     }
 }
 
 /// Perspective projection parameters
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Perspective {
     pub fov: f32,
     pub clip_near: f32,
     pub clip_far: f32,
 }
 
+/// Orthographic projection parameters
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Orthographic {
+    /// Height of the view in world units
+    pub height: f32,
+    pub clip_near: f32,
+    pub clip_far: f32,
+}
+
+/// Equirectangular projection parameters, longitude spans the width
+/// and latitude the height of the frame
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Equirectangular {
+    pub clip_near: f32,
+    pub clip_far: f32,
+}
+
 /// Arcball camera parameters
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ArcBall {
     pub pivot: Vec3,
     pub distance: f32,
@@ -81,8 +306,84 @@ pub struct ArcBall {
     pub pitch: f32,
 }
 
+/// Lens and shutter of a real camera. The clouds are marched through the lens for depth
+/// of field and over the shutter interval for motion blur, the rasterised terrain stays sharp.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicalCamera {
+    pub enabled: bool,
+    /// Focal length over the aperture diameter
+    pub f_number: f32,
+    /// In world units, sets the lens size together with the f-number
+    pub focal_length: f32,
+    /// Distance from the camera to the sharpest plane
+    pub focus_distance: f32,
+    /// Seconds the shutter stays open
+    pub shutter: f32,
+    pub iso: f32,
+    /// Lens and shutter samples per cloud ray
+    pub samples: usize,
+}
+
+/// Aperture, shutter and sensitivity that leave the exposure unchanged
+const REFERENCE_F_NUMBER: f32 = 4.0;
+const REFERENCE_SHUTTER: f32 = 1.0 / 60.0;
+const REFERENCE_ISO: f32 = 100.0;
+
+impl PhysicalCamera {
+    /// Exposure relative to f/4, 1/60 s and ISO 100 in stops, zero when disabled
+    pub fn exposure(&self) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+        let light = self.shutter / self.f_number.powi(2) * self.iso;
+        let reference = REFERENCE_SHUTTER / REFERENCE_F_NUMBER.powi(2) * REFERENCE_ISO;
+        (light / reference).log2()
+    }
+
+    /// Radius of the aperture in world units, zero for a pinhole
+    pub fn lens_radius(&self) -> f32 {
+        match self.enabled {
+            true => self.focal_length / (2.0 * self.f_number),
+            false => 0.0,
+        }
+    }
+
+    /// Number of rays through the lens per camera ray
+    pub fn samples(&self) -> usize {
+        match self.enabled && (self.lens_radius() > 0.0 || self.shutter > 0.0) {
+            true => self.samples.max(1),
+            false => 1,
+        }
+    }
+}
+
+/// Free camera position and orientation, it never rolls
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fly {
+    pub pos: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Fly camera controller parameters
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlyController {
+    /// World units per second
+    pub speed: f32,
+    pub look_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    /// Speed factor per unit of scroll
+    pub acceleration: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+}
+
 /// Arcball camera controller parameters
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ArcBallController {
     pub pan_sensitivity: f32,
     pub swivel_sensitivity: f32,
@@ -90,9 +391,70 @@ pub struct ArcBallController {
     pub closest_zoom: f32,
 }
 
-impl Perspective {
-    pub fn matrix(&self, width: f32, height: f32) -> Mat4 {
-        Mat4::perspective_rh(self.fov, width / height, self.clip_near, self.clip_far)
+impl Projection for Perspective {
+    fn matrix(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(self.fov, aspect, self.clip_near, self.clip_far)
+    }
+
+    fn ray(&self, ndc: Vec2, aspect: f32) -> (Vec3, Vec3) {
+        let tan = (self.fov * 0.5).tan();
+        let dir = Vec3::new(ndc.x * tan * aspect, ndc.y * tan, -1.0);
+        (Vec3::ZERO, dir.normalize())
+    }
+
+    fn project(&self, point: Vec3, aspect: f32) -> Option<Vec2> {
+        let depth = -point.z;
+        if !(self.clip_near..=self.clip_far).contains(&depth) {
+            return None;
+        }
+        let tan = (self.fov * 0.5).tan();
+        Some(Vec2::new(point.x / (tan * aspect), point.y / tan) / depth)
+    }
+}
+
+impl Projection for Orthographic {
+    fn matrix(&self, aspect: f32) -> Mat4 {
+        let (w, h) = (self.height * aspect * 0.5, self.height * 0.5);
+        Mat4::orthographic_rh(-w, w, -h, h, self.clip_near, self.clip_far)
+    }
+
+    fn ray(&self, ndc: Vec2, aspect: f32) -> (Vec3, Vec3) {
+        let half = Vec2::new(aspect, 1.0) * self.height * 0.5;
+        ((ndc * half).extend(0.0), Vec3::NEG_Z)
+    }
+
+    fn project(&self, point: Vec3, aspect: f32) -> Option<Vec2> {
+        if !(self.clip_near..=self.clip_far).contains(&-point.z) {
+            return None;
+        }
+        let half = Vec2::new(aspect, 1.0) * self.height * 0.5;
+        Some(Vec2::new(point.x, point.y) / half)
+    }
+}
+
+impl Projection for Equirectangular {
+    /// There is no matrix for a panorama, the overlays use a 90° perspective
+    fn matrix(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(FRAC_PI_2, aspect, self.clip_near, self.clip_far)
+    }
+
+    fn ray(&self, ndc: Vec2, _aspect: f32) -> (Vec3, Vec3) {
+        let (lon, lat) = (ndc.x * PI, ndc.y * FRAC_PI_2);
+        let dir = Vec3::new(lon.sin() * lat.cos(), lat.sin(), -lon.cos() * lat.cos());
+        (Vec3::ZERO, dir)
+    }
+
+    fn project(&self, point: Vec3, _aspect: f32) -> Option<Vec2> {
+        let distance = point.length();
+        if !(self.clip_near..=self.clip_far).contains(&distance) {
+            return None;
+        }
+        let (lon, lat) = (point.x.atan2(-point.z), (point.y / distance).asin());
+        Some(Vec2::new(lon / PI, lat / FRAC_PI_2))
+    }
+
+    fn preserves_lines(&self) -> bool {
+        false
     }
 }
 
@@ -106,12 +468,90 @@ impl ArcBall {
         Mat4::look_at_rh(eye, self.pivot, Vec3::new(0.0, 1.0, 0.0))
     }
 
+    /// Turns around the pivot so that the eye looks along `dir`
+    pub fn face(&mut self, dir: Vec3) {
+        let dir = dir.normalize();
+        self.yaw = (-dir.z).atan2(-dir.x);
+        self.pitch = (-dir.y).asin().clamp(-MAX_FLY_PITCH, MAX_FLY_PITCH);
+    }
+
     pub fn eye(&self) -> Vec3 {
+        self.pivot
+            + Vec3::new(
+                self.yaw.cos() * self.pitch.cos(),
+                self.pitch.sin(),
+                self.yaw.sin() * self.pitch.cos(),
+            ) * self.distance
+    }
+}
+
+/// Maps the unit square onto the unit disk keeping the samples evenly spread
+fn concentric_disk(u: Vec2) -> Vec2 {
+    let p = u * 2.0 - Vec2::ONE;
+    if p == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+    let (r, theta) = match p.x.abs() > p.y.abs() {
+        true => (p.x, PI / 4.0 * (p.y / p.x)),
+        false => (p.y, FRAC_PI_2 - PI / 4.0 * (p.x / p.y)),
+    };
+    Vec2::new(theta.cos(), theta.sin()) * r
+}
+
+/// Both controllers stop short of looking straight up or down, where yaw is undefined
+const MAX_FLY_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+impl Fly {
+    /// Fly camera at `pos` looking along `dir`
+    pub fn looking(pos: Vec3, dir: Vec3) -> Self {
+        let dir = dir.normalize();
+        Self {
+            pos,
+            yaw: dir.z.atan2(dir.x),
+            pitch: dir.y.asin().clamp(-MAX_FLY_PITCH, MAX_FLY_PITCH),
+        }
+    }
+
+    pub fn dir(&self) -> Vec3 {
         Vec3::new(
             self.yaw.cos() * self.pitch.cos(),
             self.pitch.sin(),
             self.yaw.sin() * self.pitch.cos(),
-        ) * self.distance
+        )
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::look_to_rh(self.pos, self.dir(), Vec3::Y)
+    }
+}
+
+impl FlyController {
+    pub fn look(&mut self, fly: &mut Fly, delta_x: f32, delta_y: f32) {
+        fly.yaw += delta_x * self.look_sensitivity;
+        fly.pitch -= delta_y * self.look_sensitivity;
+
+        fly.pitch = fly.pitch.clamp(-MAX_FLY_PITCH, MAX_FLY_PITCH);
+    }
+
+    pub fn pan(&mut self, fly: &mut Fly, delta_x: f32, delta_y: f32) {
+        let forward = fly.dir();
+        let right = forward.cross(Vec3::Y).normalize();
+        let up = right.cross(forward);
+        fly.pos += (up * delta_y - right * delta_x) * self.pan_sensitivity * self.speed;
+    }
+
+    pub fn accelerate(&mut self, delta: f32) {
+        self.speed *= self.acceleration.powf(delta);
+        self.speed = self.speed.clamp(self.min_speed, self.max_speed);
+    }
+
+    /// Moves along the right, up and forward axes of the camera, the up axis is the world one
+    /// so that climbing does not depend on where the camera looks
+    pub fn fly(&mut self, fly: &mut Fly, direction: Vec3, dt: f32) {
+        let forward = fly.dir();
+        let right = forward.cross(Vec3::Y).normalize();
+        let step = right * direction.x + Vec3::Y * direction.y + forward * direction.z;
+        fly.pos += step.normalize_or_zero() * self.speed * dt;
     }
 }
 
@@ -120,7 +560,7 @@ impl ArcBallController {
         arcball.yaw += delta_x * self.swivel_sensitivity;
         arcball.pitch += delta_y * self.swivel_sensitivity;
 
-        arcball.pitch = arcball.pitch.clamp(-FRAC_PI_2, FRAC_PI_2);
+        arcball.pitch = arcball.pitch.clamp(-MAX_FLY_PITCH, MAX_FLY_PITCH);
     }
 
     pub fn pan(&mut self, arcball: &mut ArcBall, delta_x: f32, delta_y: f32) {
@@ -155,6 +595,42 @@ impl Default for ArcBall {
     }
 }
 
+// Behind the default arcball view, under the cloud deck
+impl Default for Fly {
+    fn default() -> Self {
+        Self::looking(Vec3::new(-12.0, 1.5, -8.0), Vec3::new(12.0, 0.5, 8.0))
+    }
+}
+
+// Arbitrary
+impl Default for FlyController {
+    fn default() -> Self {
+        Self {
+            speed: 2.0,
+            look_sensitivity: 0.004,
+            pan_sensitivity: 0.01,
+            acceleration: 1.1,
+            min_speed: 0.05,
+            max_speed: 50.0,
+        }
+    }
+}
+
+// Exposure neutral, focused on the default pivot
+impl Default for PhysicalCamera {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            f_number: REFERENCE_F_NUMBER,
+            focal_length: 0.5,
+            focus_distance: 15.0,
+            shutter: REFERENCE_SHUTTER,
+            iso: REFERENCE_ISO,
+            samples: 8,
+        }
+    }
+}
+
 // Arbitrary
 impl Default for Perspective {
     fn default() -> Self {
@@ -166,6 +642,26 @@ impl Default for Perspective {
     }
 }
 
+// Frames the pivot like the default perspective
+impl Default for Orthographic {
+    fn default() -> Self {
+        Self {
+            height: 12.0,
+            clip_near: 0.1,
+            clip_far: 100.0,
+        }
+    }
+}
+
+impl Default for Equirectangular {
+    fn default() -> Self {
+        Self {
+            clip_near: 0.1,
+            clip_far: 100.0,
+        }
+    }
+}
+
 // Arbitrary
 impl Default for ArcBallController {
     fn default() -> Self {
@@ -200,7 +696,7 @@ mod tests {
                     yaw: 0.0,
                     pitch: 0.0,
                 },
-                control: Default::default(),
+                ..Default::default()
             };
 
             let dir = camera.dir();
@@ -222,7 +718,7 @@ mod tests {
                     yaw: 0.0,
                     pitch: 0.0,
                 },
-                control: Default::default(),
+                ..Default::default()
             };
 
             let dir = camera.dir();
@@ -230,6 +726,17 @@ mod tests {
             assert_eq!(dir.length(), 1.0);
         }
 
+        #[test]
+        fn test_arcball_stops_short_of_poles() {
+            let mut top = Camera::default();
+            top.pivot(0.0, 1e4);
+            assert!(top.view.pitch < FRAC_PI_2);
+            assert!(top.basis().iter().all(|axis| axis.is_finite()));
+            top.view.pitch = FRAC_PI_2;
+            assert!(top.basis().iter().all(|axis| axis.is_finite()));
+            assert!(top.ray(3.0, 4.0, 10.0, 10.0).1.is_finite());
+        }
+
         #[test]
         fn test_camera_position() {
             let camera = Camera {
@@ -244,9 +751,101 @@ mod tests {
                     yaw: 0.0,
                     pitch: 0.0,
                 },
-                control: Default::default(),
+                ..Default::default()
             };
             assert_eq!(camera.pos(), Vec3::new(10.0, 0.0, 0.0));
         }
+
+        #[test]
+        fn test_fly_controller() {
+            let mut camera = Camera::default();
+            let (pos, dir) = (camera.pos(), camera.dir());
+            camera.set_controller(ControllerKind::Fly);
+            assert!(camera.pos().abs_diff_eq(pos, 1e-5));
+            assert!(camera.dir().abs_diff_eq(dir, 1e-5));
+
+            camera.fly(Vec3::Z, 0.5);
+            let flown = pos + dir * camera.fly_control.speed * 0.5;
+            assert!(camera.pos().abs_diff_eq(flown, 1e-4));
+            camera.pivot(0.0, 1e4);
+            assert!(camera.dir().y > -1.0 && camera.dir().y < -0.99);
+
+            let (pos, dir) = (camera.pos(), camera.dir());
+            camera.set_controller(ControllerKind::ArcBall);
+            assert!(camera.pos().abs_diff_eq(pos, 1e-4));
+            assert!(camera.dir().abs_diff_eq(dir, 1e-4));
+        }
+
+        #[test]
+        fn test_framing() {
+            let bb = BoundingBox::from((Vec3::new(10.0, 2.0, 4.0), Vec3::new(14.0, 3.0, 8.0)));
+            let (w, h) = (40.0, 30.0);
+            let camera = Camera {
+                controller: ControllerKind::Fly,
+                ..Default::default()
+            }
+            .framing(&bb);
+
+            assert_eq!(camera.controller, ControllerKind::ArcBall);
+            assert!(camera.dir().abs_diff_eq(Fly::default().dir(), 1e-5));
+            for corner in bb.corners() {
+                let p = camera.project(corner, w, h).unwrap();
+                assert!(
+                    (0.0..=w).contains(&p.x) && (0.0..=h).contains(&p.y),
+                    "{p:?}"
+                );
+            }
+        }
+
+        #[test]
+        fn test_projections_round_trip() {
+            let (w, h) = (64.0, 32.0);
+            for kind in ProjectionKind::ALL {
+                let camera = Camera {
+                    kind,
+                    ..Default::default()
+                };
+                for (x, y) in [(32.0, 16.0), (3.0, 5.0), (60.0, 30.0)] {
+                    let (origin, dir) = camera.ray(x, y, w, h);
+                    assert!((dir.length() - 1.0).abs() < 1e-5);
+                    let p = camera.project(origin + dir * 5.0, w, h).unwrap();
+                    assert!(
+                        (p.x - x).abs() < 1e-3 && (p.y - y).abs() < 1e-3,
+                        "{kind:?} {p:?}"
+                    );
+                }
+                let (origin, dir) = camera.ray(w / 2.0, h / 2.0, w, h);
+                assert!(dir.abs_diff_eq(camera.dir(), 1e-5), "{kind:?}");
+                assert!(origin.abs_diff_eq(camera.pos(), 1e-5), "{kind:?}");
+            }
+        }
+
+        #[test]
+        fn test_physical_camera() {
+            let mut camera = Camera::default();
+            assert_eq!(camera.physical.exposure(), 0.0);
+            assert_eq!(camera.physical.samples(), 1);
+
+            camera.physical.enabled = true;
+            assert!(camera.physical.exposure().abs() < 1e-5);
+            camera.physical.f_number = 8.0;
+            camera.physical.iso = 200.0;
+            assert!((camera.physical.exposure() + 1.0).abs() < 1e-5);
+
+            // Rays through any point of the lens meet on the focus plane
+            let (w, h) = (64.0, 32.0);
+            let focus = camera.physical.focus_distance;
+            let (center, _) = camera.ray(10.0, 20.0, w, h);
+            let ((_, pinhole), _) = camera.lens_ray(10.0, 20.0, w, h, Vec2::splat(0.5), 0.5);
+            let target = center + pinhole * focus / pinhole.dot(camera.dir());
+            for lens in [Vec2::ZERO, Vec2::new(0.9, 0.2), Vec2::ONE] {
+                let ((origin, dir), time) = camera.lens_ray(10.0, 20.0, w, h, lens, 1.0);
+                assert!(origin.distance(center) > 0.0);
+                let hit =
+                    origin + dir * (target - origin).dot(camera.dir()) / dir.dot(camera.dir());
+                assert!(hit.abs_diff_eq(target, 1e-3), "{lens:?}");
+                assert_eq!(time, camera.physical.shutter * 0.5);
+            }
+        }
     }
 }
@@ -0,0 +1,278 @@
+//! Camera keyframes interpolated into a smooth fly-through
+
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::{Camera, ControllerKind, Fly};
+
+/// Pose of the camera at a moment of the animation
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    /// Seconds from the start of the animation
+    pub time: f32,
+    pub position: Vec3,
+    /// Point the camera looks at
+    pub target: Vec3,
+    /// Vertical field of view in radians
+    pub fov: f32,
+}
+
+impl CameraKeyframe {
+    /// Roll-free orientation looking from the position at the target. Looking straight up
+    /// or down, the top of the view faces away from the +z axis.
+    fn orientation(&self) -> Quat {
+        let forward = (self.target - self.position).normalize();
+        let up = match forward.y.abs() > 0.999 {
+            true => Vec3::NEG_Z,
+            false => Vec3::Y,
+        };
+        let right = forward.cross(up).normalize();
+        let up = right.cross(forward);
+        Quat::from_mat3(&glam::Mat3::from_cols(right, up, -forward))
+    }
+}
+
+/// Keyframes sorted by time. Positions follow a Catmull-Rom spline, orientations are
+/// slerped and the field of view is interpolated linearly.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "CameraPathFields")]
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+/// [`CameraPath`] as read from a file, the keyframes may come in any order
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CameraPathFields {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl TryFrom<CameraPathFields> for CameraPath {
+    type Error = String;
+
+    fn try_from(f: CameraPathFields) -> Result<Self, Self::Error> {
+        let mut keyframes = f.keyframes;
+        if let Some(key) = keyframes.iter().find(|k| !k.time.is_finite()) {
+            return Err(format!("keyframe time {} is not a number", key.time));
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        if let Some(pair) = keyframes
+            .windows(2)
+            .find(|pair| pair[0].time == pair[1].time)
+        {
+            return Err(format!("two keyframes at {} s", pair[0].time));
+        }
+        Ok(Self { keyframes })
+    }
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_keyframe(mut self, keyframe: CameraKeyframe) -> Self {
+        self.add_keyframe(keyframe);
+        self
+    }
+
+    /// Inserts the keyframe keeping them sorted by time
+    pub fn add_keyframe(&mut self, keyframe: CameraKeyframe) {
+        let index = self.keyframes.partition_point(|k| k.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// Pose at `time`, held at the first and the last keyframes outside of them
+    pub fn sample(&self, time: f32) -> Option<CameraKeyframe> {
+        let keys = &self.keyframes;
+        let first = keys.first()?;
+        let last = keys.last()?;
+        if keys.len() == 1 || time <= first.time {
+            return Some(*first);
+        }
+        if time >= last.time {
+            return Some(*last);
+        }
+
+        let i = keys.partition_point(|k| k.time <= time) - 1;
+        let (k1, k2) = (keys[i], keys[i + 1]);
+        let span = k2.time - k1.time;
+        if span <= 0.0 {
+            return Some(k2);
+        }
+        let s = (time - k1.time) / span;
+
+        // Tangents of a Catmull-Rom spline through unevenly spaced keys, one-sided at the ends
+        let tangent = |j: usize| {
+            let (a, b) = (keys[j.saturating_sub(1)], keys[(j + 1).min(keys.len() - 1)]);
+            (b.position - a.position) / (b.time - a.time)
+        };
+        let (m1, m2) = (tangent(i) * span, tangent(i + 1) * span);
+        let (s2, s3) = (s * s, s * s * s);
+        let position = k1.position * (2.0 * s3 - 3.0 * s2 + 1.0)
+            + m1 * (s3 - 2.0 * s2 + s)
+            + k2.position * (-2.0 * s3 + 3.0 * s2)
+            + m2 * (s3 - s2);
+
+        let orientation = k1.orientation().slerp(k2.orientation(), s);
+        Some(CameraKeyframe {
+            time,
+            position,
+            target: position + orientation * Vec3::NEG_Z,
+            fov: k1.fov + (k2.fov - k1.fov) * s,
+        })
+    }
+
+    /// `camera` flying along the path at `time`, unchanged when there are no keyframes
+    pub fn camera(&self, mut camera: Camera, time: f32) -> Camera {
+        let Some(key) = self.sample(time) else {
+            return camera;
+        };
+        camera.controller = ControllerKind::Fly;
+        camera.fly = Fly::looking(key.position, key.target - key.position);
+        camera.proj.fov = key.fov;
+        camera
+    }
+}
+
+/// Smooth move of the camera to another pose over `duration` seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraTransition {
+    path: CameraPath,
+    to: Camera,
+    elapsed: f32,
+}
+
+impl CameraTransition {
+    pub fn new(from: &Camera, to: Camera, duration: f32) -> Self {
+        let key = |camera: &Camera, time: f32| CameraKeyframe {
+            time,
+            position: camera.pos(),
+            target: camera.pos() + camera.dir(),
+            fov: camera.proj.fov,
+        };
+        Self {
+            path: CameraPath::new()
+                .with_keyframe(key(from, 0.0))
+                .with_keyframe(key(&to, duration.max(0.0))),
+            to,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.path.duration()
+    }
+
+    /// Camera after `dt` more seconds, easing in and out. Ends exactly on the target camera.
+    /// The camera keeps the controller of the target all the way, so that the move can be
+    /// taken over by hand at any moment.
+    pub fn advance(&mut self, dt: f32) -> Camera {
+        self.elapsed += dt;
+        if self.finished() {
+            return self.to;
+        }
+        let s = self.elapsed / self.path.duration();
+        let eased = s * s * (3.0 - 2.0 * s);
+        let mut camera = self.path.camera(self.to, eased * self.path.duration());
+        camera.set_controller(self.to.controller);
+        camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_interpolation() {
+        let key = |time: f32, x: f32, fov: f32| CameraKeyframe {
+            time,
+            position: Vec3::new(x, 1.0, 0.0),
+            target: Vec3::new(x, 1.0, -1.0),
+            fov,
+        };
+        let path = CameraPath::new()
+            .with_keyframe(key(2.0, 2.0, 0.5))
+            .with_keyframe(key(0.0, 0.0, 0.5))
+            .with_keyframe(key(1.0, 1.0, 1.0));
+
+        assert_eq!(path.duration(), 2.0);
+        assert_eq!(path.sample(-1.0), Some(key(0.0, 0.0, 0.5)));
+        assert_eq!(path.sample(1.0).unwrap().position, Vec3::new(1.0, 1.0, 0.0));
+
+        // Evenly spaced collinear keys move at a constant speed
+        let mid = path.sample(0.25).unwrap();
+        assert!(mid.position.abs_diff_eq(Vec3::new(0.25, 1.0, 0.0), 1e-5));
+        assert!((mid.fov - 0.625).abs() < 1e-5);
+
+        let camera = path.camera(Camera::default(), 1.5);
+        assert!(camera.dir().abs_diff_eq(Vec3::NEG_Z, 1e-4));
+        assert!(camera.pos().abs_diff_eq(Vec3::new(1.5, 1.0, 0.0), 1e-5));
+
+        // A top-down shot has no roll-free orientation around the world up axis
+        let top_down = CameraKeyframe {
+            time: 3.0,
+            position: Vec3::new(0.0, 10.0, 0.0),
+            target: Vec3::ZERO,
+            fov: 0.5,
+        };
+        let path = path.with_keyframe(top_down);
+        for time in [2.5, 2.99] {
+            let key = path.sample(time).unwrap();
+            assert!(key.target.is_finite(), "{time} {key:?}");
+            assert!(path.camera(Camera::default(), time).dir().is_finite());
+        }
+        let near_end = path.sample(2.99).unwrap();
+        assert!((near_end.target - near_end.position).y < -0.99);
+    }
+
+    #[test]
+    fn test_keyframes_are_sorted_when_read() {
+        let key = |time: f32| CameraKeyframe {
+            time,
+            position: Vec3::new(time, 1.0, 0.0),
+            target: Vec3::new(time, 1.0, -1.0),
+            fov: 0.5,
+        };
+        let parse = |times: &[f32]| {
+            let keyframes = times.iter().map(|&t| key(t)).collect();
+            let json = serde_json::to_string(&CameraPath { keyframes }).unwrap();
+            serde_json::from_str::<CameraPath>(&json)
+        };
+
+        let path = parse(&[2.0, 0.0, 1.0]).unwrap();
+        assert_eq!(path.keyframes, vec![key(0.0), key(1.0), key(2.0)]);
+        assert!(path
+            .sample(0.5)
+            .unwrap()
+            .position
+            .abs_diff_eq(Vec3::new(0.5, 1.0, 0.0), 1e-5));
+        assert!(parse(&[1.0, 0.0, 1.0]).is_err());
+        assert!(serde_json::from_str::<CameraPath>("{}")
+            .unwrap()
+            .keyframes
+            .is_empty());
+    }
+
+    #[test]
+    fn test_transition_ends_on_target() {
+        let from = Camera::default();
+        let mut to = from;
+        to.view.yaw += 1.0;
+        to.view.distance = 5.0;
+
+        let mut transition = CameraTransition::new(&from, to, 1.0);
+        let halfway = transition.advance(0.5);
+        assert!(!transition.finished());
+        assert!(halfway.pos().distance(from.pos()) > 0.1);
+        assert!(halfway.pos().distance(to.pos()) > 0.1);
+        assert_eq!(transition.advance(0.5), to);
+        assert!(transition.finished());
+    }
+}
@@ -0,0 +1,83 @@
+//! Several views of the scene rendered together, e.g. a stereo pair for a VR viewer
+
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::Camera;
+
+/// Cameras derived from the main one, their images are laid out in a grid
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CameraRig {
+    #[default]
+    Mono,
+    /// Left and right eyes side by side with parallel axes, `eye_separation` apart
+    Stereo { eye_separation: f32 },
+    /// `columns` x `rows` cameras `spacing` apart across the view plane,
+    /// row by row from the top left one
+    Grid {
+        columns: usize,
+        rows: usize,
+        spacing: f32,
+    },
+}
+
+impl CameraRig {
+    /// Number of views across and down
+    pub fn layout(&self) -> [usize; 2] {
+        match *self {
+            CameraRig::Mono => [1, 1],
+            CameraRig::Stereo { .. } => [2, 1],
+            CameraRig::Grid { columns, rows, .. } => [columns.max(1), rows.max(1)],
+        }
+    }
+
+    /// Cameras of the views in the order of the layout, centred on `camera`
+    pub fn cameras(&self, camera: &Camera) -> Vec<Camera> {
+        let (spacing, [columns, rows]) = match *self {
+            CameraRig::Mono => return vec![*camera],
+            CameraRig::Stereo { eye_separation } => (eye_separation, self.layout()),
+            CameraRig::Grid { spacing, .. } => (spacing, self.layout()),
+        };
+        let [right, up, _] = camera.basis();
+        let center = |n: usize, i: usize| i as f32 - (n - 1) as f32 / 2.0;
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let mut view = *camera;
+                view.translate(
+                    (right * center(columns, column) - up * center(rows, row)) * spacing,
+                );
+                view
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stereo_pair() {
+        let camera = Camera::default();
+        let rig = CameraRig::Stereo {
+            eye_separation: 0.5,
+        };
+        let [left, right] = rig.cameras(&camera)[..] else {
+            panic!("a stereo rig has two cameras");
+        };
+        let [axis, ..] = camera.basis();
+        assert!((right.pos() - left.pos()).abs_diff_eq(axis * 0.5, 1e-5));
+        assert!((left.pos() + right.pos()).abs_diff_eq(camera.pos() * 2.0, 1e-5));
+        assert!(left.dir().abs_diff_eq(camera.dir(), 1e-5));
+
+        let grid = CameraRig::Grid {
+            columns: 3,
+            rows: 2,
+            spacing: 1.0,
+        };
+        assert_eq!(grid.layout(), [3, 2]);
+        let cameras = grid.cameras(&camera);
+        assert_eq!(cameras.len(), 6);
+        assert!(cameras[1].pos().y > cameras[4].pos().y);
+    }
+}
@@ -2,11 +2,13 @@ use glam::Vec3;
 use objects::cloud::Cloud;
 
 use crate::object::camera::Camera;
-use crate::object::objects::{Grid, Sun, Terrain};
+use crate::object::objects::{BoundingBox, Grid, Moon, Sky, Sun, Terrain};
 use crate::scene::scene_composite::SceneObjects;
 use crate::visitor::{Visitable, Visitor};
 
 pub mod camera;
+pub mod camera_path;
+pub mod camera_rig;
 pub mod objects;
 
 #[derive(Debug)]
@@ -15,6 +17,8 @@ pub enum Component {
     Composite(SceneObjects),
     Cloud(Box<Cloud>),
     Sun(Sun),
+    Moon(Moon),
+    Sky(Box<Sky>),
     Grid(Grid),
     Terrain(Box<Terrain>),
 }
@@ -36,10 +40,21 @@ impl Component {
             Component::Composite(_) => Vec3::ZERO,
             Component::Cloud(x) => x.bounding_box.center(),
             Component::Sun(x) => x.get_pos(),
+            Component::Moon(x) => x.dir(),
+            Component::Sky(_) => Vec3::ZERO,
             Component::Grid(_) => Vec3::ZERO,
             Component::Terrain(x) => x.bounding_box.center(),
         }
     }
+
+    /// Box around the object, `None` for the lights, the sky and the grid
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        match self {
+            Component::Cloud(x) => Some(x.bounding_box),
+            Component::Terrain(x) => Some(x.bounding_box),
+            _ => None,
+        }
+    }
 }
 
 impl From<Camera> for Component {
@@ -66,6 +81,18 @@ impl From<Sun> for Component {
     }
 }
 
+impl From<Moon> for Component {
+    fn from(value: Moon) -> Self {
+        Component::Moon(value)
+    }
+}
+
+impl From<Sky> for Component {
+    fn from(value: Sky) -> Self {
+        Component::Sky(Box::new(value))
+    }
+}
+
 impl From<Terrain> for Component {
     fn from(value: Terrain) -> Self {
         Component::Terrain(Box::new(value))
@@ -80,6 +107,8 @@ impl Visitable for Component {
             Component::Cloud(cloud) => cloud.accept(visitor),
             Component::Grid(grid) => grid.accept(visitor),
             Component::Sun(sun) => sun.accept(visitor),
+            Component::Moon(moon) => moon.accept(visitor),
+            Component::Sky(sky) => sky.accept(visitor),
             Component::Terrain(ter) => ter.accept(visitor),
         }
     }
@@ -1,7 +1,9 @@
 use crate::visitor::{Visitable, Visitor};
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BoundingBox {
     /// One of the corners of the rectangle, usually the left top one.
     pub min: Vec3,
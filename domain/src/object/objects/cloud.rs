@@ -2,13 +2,18 @@ use std::fmt::{Debug, Formatter};
 use std::ops::{Deref, DerefMut};
 
 use crate::object::objects::texture3d::{INoise, INoiseBuilder, Noise, NoiseBuilder};
+use crate::object::objects::textures::slice::CloudTexture;
 use crate::visitor::{Visitable, Visitor};
 use egui::Color32;
 use glam::{FloatExt, IVec3, Vec3, Vec3Swizzles, Vec4};
 use log::info;
+use serde::{Deserialize, Serialize};
 
 use super::BoundingBox;
 
+const BASE_SCALE: f32 = 1.0 / 1000.0;
+const OFFSET_SPEED: f32 = 1.0 / 100.0;
+
 #[inline]
 pub fn remap(v: f32, min_old: f32, max_old: f32, min_new: f32, max_new: f32) -> f32 {
     min_new + (v - min_old) * (max_new - min_new) / (max_old - min_old)
@@ -32,10 +37,13 @@ pub fn beer(d: f32) -> f32 {
     (-d).exp()
 }
 
-#[derive(Default, Debug, Copy,Clone)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CloudBuilder {
     pub bounding_box: BoundingBox,
     pub offset: Vec3,
+    /// Change of the offset per second, blurs the clouds over the shutter interval
+    pub wind: Vec3,
     pub cloud_scale: f32,
     pub density_threshold: f32,
     pub density_offset: f32,
@@ -62,7 +70,6 @@ pub struct CloudBuilder {
     pub light_absorption_toward_sun: f32,
     pub light_absorption_through_cloud: f32,
     pub darkness_threshold: f32,
-    pub light_color: Color32,
     pub col_a: Color32,
     pub col_b: Color32,
     pub noise: NoiseBuilder,
@@ -103,6 +110,11 @@ impl CloudBuilder {
         self
     }
 
+    pub fn with_wind(mut self, wind: Vec3) -> Self {
+        self.wind = wind;
+        self
+    }
+
     pub fn with_cloud_scale(mut self, cloud_scale: f32) -> Self {
         self.cloud_scale = cloud_scale;
         self
@@ -206,11 +218,6 @@ impl CloudBuilder {
         self
     }
 
-    pub fn with_light_color(mut self, light_color: Color32) -> Self {
-        self.light_color = light_color;
-        self
-    }
-
     pub fn with_col_a(mut self, col_a: Color32) -> Self {
         self.col_a = col_a;
         self
@@ -290,16 +297,38 @@ impl Cloud {
         &self.bounding_box
     }
 
+    /// World-space shift of the cloud shapes since the cloud had `prev_offset`
+    pub fn drift(&self, prev_offset: Vec3) -> Vec3 {
+        (prev_offset - self.offset) * OFFSET_SPEED / (self.cloud_scale * BASE_SCALE)
+    }
+
+    pub fn texture(&self, texture: CloudTexture) -> &Noise {
+        match texture {
+            CloudTexture::Shape => &self.noise,
+            CloudTexture::Detail => &self.detail_noise,
+            CloudTexture::Weather => &self.weather_map,
+        }
+    }
+
+    /// Weather map value above the point, it sets how low and how high the cloud reaches
+    pub fn weather(&self, pos: Vec3) -> f32 {
+        let bb = self.bounding_box();
+        let size = bb.size();
+        let weather_uv = (size.xz() * 0.5 + (pos.xz() - bb.center().xz())) / size.x.max(size.z);
+        self.weather_map.sample_level(Vec3::new(weather_uv.x, 0.0, weather_uv.y)).x * 0.5
+    }
+
     pub fn sample_density(&self, ray_pos: Vec3) -> f32 {
-        const BASE_SCALE: f32 = 1.0 / 1000.0;
-        const OFFSET_SPEED: f32 = 1.0 / 100.0;
+        self.sample_density_at(ray_pos, self.offset)
+    }
 
-        let uvw = ray_pos * self.cloud_scale * BASE_SCALE + self.offset * OFFSET_SPEED;
+    /// Density as it would be with the given offset, for sampling the clouds in motion
+    pub fn sample_density_at(&self, ray_pos: Vec3, offset: Vec3) -> f32 {
+        let uvw = ray_pos * self.cloud_scale * BASE_SCALE + offset * OFFSET_SPEED;
         let shape = self.noise.sample_level(uvw).abs();
 
         let bb = self.bounding_box();
         let size = bb.size();
-        let center = bb.center();
         let container_edge_fade_dst = self.edge_distance;
 
         let dst_from_edge_x = (ray_pos.x - bb.min.x)
@@ -310,8 +339,7 @@ impl Cloud {
             .min(container_edge_fade_dst);
         let edge_weight = (dst_from_edge_x.min(dst_from_edge_z)) / container_edge_fade_dst;
 
-        let weather_uv = (size.xz() * 0.5 + (ray_pos.xz() - center.xz())) / size.x.max(size.z);
-        let weather_map = self.weather_map.sample_level(Vec3::new(weather_uv.x, 0.0, weather_uv.y)).x * 0.5;
+        let weather_map = self.weather(ray_pos);
         // println!("{:?}", weather_map);
         let g_min = weather_map.remap(0.0, 1.0, 0.1, 0.5);
         let g_max = weather_map.remap(0.0, 1.0, g_min, 0.9);
@@ -333,7 +361,7 @@ impl Cloud {
         if base_shape_density > 0.0 {
             let detail_sample_pos = uvw * self.detail_noise_scale
                 + self.detail_offset * OFFSET_SPEED
-                + self.offset * OFFSET_SPEED;
+                + offset * OFFSET_SPEED;
             let detail_noise = self.detail_noise.sample_level(detail_sample_pos).abs();
 
             let normalized_detail_weights =
@@ -352,7 +380,12 @@ impl Cloud {
         0.0
     }
 
-    pub fn light_march(&self, mut p: Vec3, world_space_light_pos0: Vec3) -> f32 {
+    pub fn light_march(&self, p: Vec3, world_space_light_pos0: Vec3) -> f32 {
+        self.light_march_at(p, world_space_light_pos0, self.offset)
+    }
+
+    /// Light transmittance with the given offset, see [`Cloud::sample_density_at`]
+    pub fn light_march_at(&self, mut p: Vec3, world_space_light_pos0: Vec3, offset: Vec3) -> f32 {
         let dir_to_light = world_space_light_pos0;
         let dst_inside_box = self.bounding_box().dst(p, dir_to_light).y;
         let step_size = dst_inside_box / self.num_steps_light as f32;
@@ -362,7 +395,7 @@ impl Cloud {
         let step_size_f32 = step_size;
 
         for _ in 0..self.num_steps_light {
-            let density = self.sample_density(p);
+            let density = self.sample_density_at(p, offset);
             total_density += density.max(0.0);
             p += dir_to_light * step_size_f32;
         }
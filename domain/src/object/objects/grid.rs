@@ -1,7 +1,8 @@
 use crate::visitor::{Visitable, Visitor};
 pub use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Grid {
     pub k: i32,
     pub scale: f32,
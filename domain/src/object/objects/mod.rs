@@ -1,13 +1,17 @@
 pub use bounding_box::BoundingBox;
 pub use cloud::Cloud;
 pub use grid::Grid;
-pub use sun::Sun;
+pub use moon::Moon;
+pub use sky::Sky;
+pub use sun::{DirectionalLight, Sun};
 pub use terrain::Terrain;
 pub use textures::texture3d;
 
 pub mod bounding_box;
 pub mod cloud;
 pub mod grid;
+pub mod moon;
+pub mod sky;
 pub mod sun;
 pub mod terrain;
 pub mod textures;
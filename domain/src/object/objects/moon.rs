@@ -0,0 +1,140 @@
+use std::f32::consts::TAU;
+
+use egui::Color32;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use super::DirectionalLight;
+use crate::canvas::color;
+use crate::visitor::{Visitable, Visitor};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Moon {
+    /// Angle above the horizon in degrees
+    pub elevation: f32,
+    /// Degrees, uses the same convention as [`super::Sun::set_solar_position`]
+    pub azimuth: f32,
+    /// Lunar cycle position, 0 and 1 are the new moon, 0.5 is the full moon
+    pub phase: f32,
+    pub color: Color32,
+    /// Brightness of the full moon relative to a unit intensity sun
+    pub intensity: f32,
+    /// Apparent diameter of the disk in degrees
+    pub angular_diameter: f32,
+}
+
+impl Default for Moon {
+    fn default() -> Self {
+        Self::new(45.0, 180.0, 0.5)
+    }
+}
+
+impl Moon {
+    pub fn new(elevation: f32, azimuth: f32, phase: f32) -> Self {
+        Self {
+            elevation,
+            azimuth,
+            phase,
+            color: Color32::from_rgb(200, 210, 255),
+            intensity: 0.05,
+            angular_diameter: 0.52,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_angular_diameter(mut self, angular_diameter: f32) -> Self {
+        self.angular_diameter = angular_diameter;
+        self
+    }
+
+    /// Unit vector pointing toward the moon
+    pub fn dir(&self) -> Vec3 {
+        let (e, a) = (self.elevation.to_radians(), self.azimuth.to_radians());
+        Vec3::new(-e.cos() * a.cos(), e.sin(), e.cos() * a.sin())
+    }
+
+    /// Angular radius of the disk in radians
+    #[inline]
+    pub fn angular_radius(&self) -> f32 {
+        (self.angular_diameter * 0.5).to_radians()
+    }
+
+    /// Fraction of the visible disk lit by the sun
+    #[inline]
+    pub fn illumination(&self) -> f32 {
+        (1.0 - (self.phase * TAU).cos()) * 0.5
+    }
+
+    /// Direction of the sunlight falling on the moon, derived from the phase
+    pub fn sunlight_dir(&self) -> Vec3 {
+        let dir = self.dir();
+        let tangent = dir.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+        let elongation = self.phase * TAU;
+        dir * elongation.cos() + tangent * elongation.sin()
+    }
+
+    pub fn light(&self) -> DirectionalLight {
+        DirectionalLight {
+            dir: self.dir(),
+            radiance: color::to_linear_rgb(self.color) * self.intensity * self.illumination(),
+        }
+    }
+
+    /// Brightness of the disk along `ray_dir`, zero outside of the lit part
+    pub fn disk(&self, ray_dir: Vec3) -> f32 {
+        let dir = self.dir();
+        let r = ray_dir.dot(dir).clamp(-1.0, 1.0).acos() / self.angular_radius();
+        if r >= 1.0 {
+            return 0.0;
+        }
+        let offset = (ray_dir - dir * ray_dir.dot(dir)).normalize_or_zero();
+        let normal = -dir * (1.0 - r * r).sqrt() + offset * r;
+        let lit = normal.dot(self.sunlight_dir());
+        (lit * 8.0).clamp(0.0, 1.0)
+    }
+
+    pub fn set_angle(&mut self, elevation: f32, azimuth: f32) {
+        self.elevation = elevation;
+        self.azimuth = azimuth;
+    }
+
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+}
+
+impl Visitable for Moon {
+    fn accept(&self, visitor: &mut impl Visitor) {
+        visitor.visit_moon(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moon_phases() {
+        let moon = Moon::new(30.0, 90.0, 0.5);
+        assert!((moon.illumination() - 1.0).abs() < 1e-5);
+        assert!(moon.disk(moon.dir()) > 0.9);
+
+        let moon = Moon::new(30.0, 90.0, 0.0);
+        assert!(moon.illumination() < 1e-5);
+        assert_eq!(moon.disk(moon.dir()), 0.0);
+    }
+}
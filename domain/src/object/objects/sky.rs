@@ -0,0 +1,439 @@
+use std::f32::consts::PI;
+use std::ops::{Deref, DerefMut};
+
+use glam::{IVec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::DirectionalLight;
+use crate::visitor::{Visitable, Visitor};
+
+/// Rayleigh scattering coefficients at sea level, 1/m
+const RAYLEIGH_BETA: Vec3 = Vec3::new(5.802e-6, 13.558e-6, 33.1e-6);
+/// Mie scattering coefficient at sea level per unit of turbidity, 1/m
+const MIE_BETA_PER_TURBIDITY: f32 = 1.05e-5;
+/// Mie extinction is slightly larger than scattering because of aerosol absorption
+const MIE_EXTINCTION_RATIO: f32 = 1.11;
+
+/// Samples along the view ray for aerial perspective
+const AERIAL_SAMPLES: usize = 4;
+
+/// Moonless night sky radiance from airglow and scattered starlight
+const AIRGLOW: Vec3 = Vec3::new(0.0015, 0.002, 0.004);
+
+/// Cells per unit of direction used to scatter stars
+const STAR_GRID: f32 = 400.0;
+/// Fraction of cells that hold a star
+const STAR_DENSITY: f32 = 0.06;
+/// Star radius in cells
+const STAR_RADIUS: f32 = 0.15;
+
+/// Directions used to estimate the ambient sky light
+const AMBIENT_DIRS: [Vec3; 5] = [
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.707, 0.707, 0.0),
+    Vec3::new(-0.707, 0.707, 0.0),
+    Vec3::new(0.0, 0.707, 0.707),
+    Vec3::new(0.0, 0.707, -0.707),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SkyBuilder {
+    /// Haziness of the atmosphere, 1 is a perfectly clear sky
+    pub turbidity: f32,
+    pub ground_albedo: Vec3,
+    /// Planet radius in metres
+    pub planet_radius: f32,
+    /// Atmosphere thickness in metres
+    pub atmosphere_height: f32,
+    pub rayleigh_scale_height: f32,
+    pub mie_scale_height: f32,
+    /// Mie phase function asymmetry
+    pub mie_g: f32,
+    /// Scale of the scattered sky radiance relative to the direct light
+    pub intensity: f32,
+    /// Metres per scene unit
+    pub world_scale: f32,
+    /// Multiplier of the view distance used for aerial perspective
+    pub aerial_strength: f32,
+    pub num_samples: usize,
+    pub num_samples_light: usize,
+}
+
+impl Default for SkyBuilder {
+    fn default() -> Self {
+        Self {
+            turbidity: 2.0,
+            ground_albedo: Vec3::splat(0.3),
+            planet_radius: 6_360_000.0,
+            atmosphere_height: 100_000.0,
+            rayleigh_scale_height: 8_000.0,
+            mie_scale_height: 1_200.0,
+            mie_g: 0.76,
+            intensity: 20.0,
+            world_scale: 1_000.0,
+            aerial_strength: 1.0,
+            num_samples: 16,
+            num_samples_light: 8,
+        }
+    }
+}
+
+impl SkyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn build(self) -> Sky {
+        Sky::build(self)
+    }
+
+    pub fn with_turbidity(mut self, turbidity: f32) -> Self {
+        self.turbidity = turbidity;
+        self
+    }
+
+    pub fn with_ground_albedo(mut self, ground_albedo: Vec3) -> Self {
+        self.ground_albedo = ground_albedo;
+        self
+    }
+
+    pub fn with_planet_radius(mut self, planet_radius: f32) -> Self {
+        self.planet_radius = planet_radius;
+        self
+    }
+
+    pub fn with_atmosphere_height(mut self, atmosphere_height: f32) -> Self {
+        self.atmosphere_height = atmosphere_height;
+        self
+    }
+
+    pub fn with_mie_g(mut self, mie_g: f32) -> Self {
+        self.mie_g = mie_g;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_world_scale(mut self, world_scale: f32) -> Self {
+        self.world_scale = world_scale;
+        self
+    }
+
+    pub fn with_aerial_strength(mut self, aerial_strength: f32) -> Self {
+        self.aerial_strength = aerial_strength;
+        self
+    }
+
+    pub fn with_num_samples(mut self, num_samples: usize) -> Self {
+        self.num_samples = num_samples;
+        self
+    }
+
+    pub fn with_num_samples_light(mut self, num_samples_light: usize) -> Self {
+        self.num_samples_light = num_samples_light;
+        self
+    }
+}
+
+/// Single scattering Rayleigh/Mie atmosphere
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Sky {
+    pub sky_builder: SkyBuilder,
+}
+
+impl Deref for Sky {
+    type Target = SkyBuilder;
+    fn deref(&self) -> &Self::Target {
+        &self.sky_builder
+    }
+}
+
+impl DerefMut for Sky {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.sky_builder
+    }
+}
+
+impl Sky {
+    pub fn build(sky_builder: SkyBuilder) -> Self {
+        Self { sky_builder }
+    }
+
+    #[inline]
+    fn mie_beta(&self) -> f32 {
+        MIE_BETA_PER_TURBIDITY * self.turbidity
+    }
+
+    /// Position in planet space of a point at the given scene height
+    #[inline]
+    pub fn origin(&self, height: f32) -> Vec3 {
+        Vec3::new(
+            0.0,
+            self.planet_radius + (height * self.world_scale).max(1.0),
+            0.0,
+        )
+    }
+
+    #[inline]
+    fn extinction(&self, rayleigh_depth: f32, mie_depth: f32) -> Vec3 {
+        let tau = RAYLEIGH_BETA * rayleigh_depth
+            + Vec3::splat(self.mie_beta() * MIE_EXTINCTION_RATIO * mie_depth);
+        (-tau).exp()
+    }
+
+    /// Rayleigh and Mie optical depth from `origin` to the top of the atmosphere,
+    /// `None` if the planet is in the way
+    fn optical_depth(&self, origin: Vec3, dir: Vec3, num_samples: usize) -> Option<(f32, f32)> {
+        if ray_sphere(origin, dir, self.planet_radius).is_some_and(|(t0, _)| t0 > 0.0) {
+            return None;
+        }
+        let (_, t1) = ray_sphere(origin, dir, self.planet_radius + self.atmosphere_height)?;
+        let step = t1.max(0.0) / num_samples as f32;
+
+        let mut depth = (0.0, 0.0);
+        for i in 0..num_samples {
+            let p = origin + dir * step * (i as f32 + 0.5);
+            let h = p.length() - self.planet_radius;
+            depth.0 += (-h / self.rayleigh_scale_height).exp() * step;
+            depth.1 += (-h / self.mie_scale_height).exp() * step;
+        }
+        Some(depth)
+    }
+
+    /// Fraction of the sunlight that reaches the given scene height
+    pub fn transmittance(&self, height: f32, sun_dir: Vec3) -> Vec3 {
+        self.optical_depth(self.origin(height), sun_dir, self.num_samples_light)
+            .map_or(Vec3::ZERO, |(r, m)| self.extinction(r, m))
+    }
+
+    /// Radiance of the direct light at the given scene height
+    pub fn sun_light(&self, height: f32, light: DirectionalLight) -> Vec3 {
+        self.transmittance(height, light.dir) * light.radiance
+    }
+
+    /// Average sky radiance over the upper hemisphere
+    pub fn ambient(&self, height: f32, light: DirectionalLight) -> Vec3 {
+        AMBIENT_DIRS
+            .iter()
+            .map(|dir| self.radiance(height, dir.normalize(), light))
+            .sum::<Vec3>()
+            / AMBIENT_DIRS.len() as f32
+    }
+
+    /// Radiance scattered toward the viewer at the given scene height looking along `dir`
+    pub fn radiance(&self, height: f32, dir: Vec3, light: DirectionalLight) -> Vec3 {
+        let sun_dir = light.dir;
+        let origin = self.origin(height);
+        let Some((t0, t1)) = ray_sphere(origin, dir, self.planet_radius + self.atmosphere_height)
+        else {
+            return Vec3::ZERO;
+        };
+        let t_start = t0.max(0.0);
+        let ground = ray_sphere(origin, dir, self.planet_radius)
+            .map(|(t0, _)| t0)
+            .filter(|&t| t > 0.0);
+        let t_end = ground.unwrap_or(t1);
+        let step = (t_end - t_start) / self.num_samples as f32;
+
+        let mut depth = (0.0, 0.0);
+        let mut rayleigh = Vec3::ZERO;
+        let mut mie = Vec3::ZERO;
+        for i in 0..self.num_samples {
+            let p = origin + dir * (t_start + step * (i as f32 + 0.5));
+            let h = p.length() - self.planet_radius;
+            let hr = (-h / self.rayleigh_scale_height).exp() * step;
+            let hm = (-h / self.mie_scale_height).exp() * step;
+            depth.0 += hr;
+            depth.1 += hm;
+
+            if let Some((lr, lm)) = self.optical_depth(p, sun_dir, self.num_samples_light) {
+                let attenuation = self.extinction(depth.0 + lr, depth.1 + lm);
+                rayleigh += attenuation * hr;
+                mie += attenuation * hm;
+            }
+        }
+
+        let mut col = self.in_scattering(rayleigh, mie, dir.dot(sun_dir));
+
+        if let Some(t) = ground {
+            let hit = origin + dir * t;
+            let normal = hit.normalize();
+            let sun_at_ground = self
+                .optical_depth(hit, sun_dir, self.num_samples_light)
+                .map_or(Vec3::ZERO, |(r, m)| self.extinction(r, m));
+            let view = self.extinction(depth.0, depth.1);
+            col += self.ground_albedo / PI * normal.dot(sun_dir).max(0.0) * sun_at_ground * view;
+        }
+
+        col * light.radiance * self.intensity
+    }
+
+    /// Dim radiance of the sky without the sun or the moon
+    pub fn night_glow(&self, height: f32, dir: Vec3) -> Vec3 {
+        AIRGLOW * self.transmittance(height, dir.with_y(dir.y.max(0.05)).normalize())
+    }
+
+    /// Light scattered toward the viewer given the accumulated Rayleigh and Mie densities
+    #[inline]
+    fn in_scattering(&self, rayleigh: Vec3, mie: Vec3, mu: f32) -> Vec3 {
+        let phase_r = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+        let phase_m = cornette_shanks(mu, self.mie_g);
+        rayleigh * RAYLEIGH_BETA * phase_r + mie * self.mie_beta() * phase_m
+    }
+
+    /// Prepares aerial perspective for a viewer at the given scene height
+    pub fn aerial_perspective(&self, height: f32, light: DirectionalLight) -> AerialPerspective {
+        AerialPerspective {
+            sky: *self,
+            origin: self.origin(height),
+            sun_dir: light.dir,
+            sun_light: self.sun_light(height, light) * self.intensity,
+        }
+    }
+}
+
+/// Extinction and in-scattering between a viewer and a visible surface
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AerialPerspective {
+    sky: Sky,
+    origin: Vec3,
+    sun_dir: Vec3,
+    sun_light: Vec3,
+}
+
+impl AerialPerspective {
+    /// Returns the transmittance and in-scattered radiance along `dir` over `distance` scene units.
+    /// The sunlight is assumed constant along the segment.
+    pub fn segment(&self, dir: Vec3, distance: f32) -> (Vec3, Vec3) {
+        let sky = &self.sky;
+        let step = distance * sky.world_scale * sky.aerial_strength / AERIAL_SAMPLES as f32;
+
+        let mut depth = (0.0, 0.0);
+        let mut rayleigh = Vec3::ZERO;
+        let mut mie = Vec3::ZERO;
+        for i in 0..AERIAL_SAMPLES {
+            let p = self.origin + dir * step * (i as f32 + 0.5);
+            let h = p.length() - sky.planet_radius;
+            let hr = (-h / sky.rayleigh_scale_height).exp() * step;
+            let hm = (-h / sky.mie_scale_height).exp() * step;
+            depth.0 += hr;
+            depth.1 += hm;
+
+            let attenuation = sky.extinction(depth.0, depth.1);
+            rayleigh += attenuation * hr;
+            mie += attenuation * hm;
+        }
+
+        let in_scattering =
+            sky.in_scattering(rayleigh, mie, dir.dot(self.sun_dir)) * self.sun_light;
+        (sky.extinction(depth.0, depth.1), in_scattering)
+    }
+}
+
+/// Brightness of the star field along `dir`, mostly zero
+pub fn star_field(dir: Vec3) -> f32 {
+    let p = dir * STAR_GRID;
+    let cell = p.floor().as_ivec3();
+    if hash(cell, 0) > STAR_DENSITY {
+        return 0.0;
+    }
+    let jitter = Vec3::new(hash(cell, 1), hash(cell, 2), hash(cell, 3)) - 0.5;
+    let star = (cell.as_vec3() + 0.5 + jitter * 0.6).normalize() * STAR_GRID;
+    let falloff = (1.0 - p.distance(star) / STAR_RADIUS).max(0.0);
+    falloff * hash(cell, 4).powi(3)
+}
+
+/// Integer hash of a lattice cell mapped to `0.0..=1.0`
+#[inline]
+fn hash(cell: IVec3, salt: u32) -> f32 {
+    let mut h = (cell.x as u32).wrapping_mul(0x8da6_b343)
+        ^ (cell.y as u32).wrapping_mul(0xd816_3841)
+        ^ (cell.z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ salt.wrapping_mul(0x1656_67b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+#[inline]
+fn cornette_shanks(mu: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu))
+        / ((2.0 + g2) * (1.0 + g2 - 2.0 * g * mu).powf(1.5))
+}
+
+/// Distances to the intersections of a ray with a sphere centred at the origin
+fn ray_sphere(origin: Vec3, dir: Vec3, radius: f32) -> Option<(f32, f32)> {
+    let b = origin.dot(dir);
+    let c = origin.dot(origin) - radius * radius;
+    let d = b * b - c;
+    if d < 0.0 {
+        return None;
+    }
+    let d = d.sqrt();
+    let (t0, t1) = (-b - d, -b + d);
+    (t1 > 0.0).then_some((t0, t1))
+}
+
+impl Visitable for Sky {
+    fn accept(&self, visitor: &mut impl Visitor) {
+        visitor.visit_sky(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sky_is_blue_at_noon() {
+        let sky = Sky::default();
+        let light = DirectionalLight {
+            dir: Vec3::Y,
+            radiance: Vec3::ONE,
+        };
+        let col = sky.radiance(0.0, Vec3::Y, light);
+        assert!(col.z > col.y && col.y > col.x, "{:?}", col);
+    }
+
+    #[test]
+    fn test_sunset_light_is_red() {
+        let sky = Sky::default();
+        let noon = sky.transmittance(0.0, Vec3::Y);
+        let sunset = sky.transmittance(0.0, Vec3::new(1.0, 0.02, 0.0).normalize());
+        assert!(noon.min_element() > 0.5);
+        assert!(sunset.x > sunset.z);
+        assert!(sunset.length() < noon.length());
+    }
+
+    #[test]
+    fn test_aerial_perspective_fades_with_distance() {
+        let sky = Sky::default();
+        let light = DirectionalLight {
+            dir: Vec3::Y,
+            radiance: Vec3::ONE,
+        };
+        let ap = sky.aerial_perspective(0.5, light);
+        let (near, _) = ap.segment(Vec3::X, 0.1);
+        let (far, far_in) = ap.segment(Vec3::X, 50.0);
+        assert!(near.min_element() > far.max_element());
+        assert!(far_in.z > far_in.x);
+        assert_eq!(ap.segment(Vec3::X, 0.0).0, Vec3::ONE);
+    }
+
+    #[test]
+    fn test_sun_below_horizon_is_dark() {
+        let sky = Sky::default();
+        assert_eq!(
+            sky.transmittance(0.0, Vec3::new(1.0, -0.5, 0.0).normalize()),
+            Vec3::ZERO
+        );
+    }
+}
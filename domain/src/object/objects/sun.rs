@@ -1,24 +1,98 @@
+use egui::Color32;
 use glam::{Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
 
+use crate::canvas::color;
+use crate::math::{SolarPosition, TimeOfDay};
 use crate::visitor::{Visitable, Visitor};
 
+/// Light arriving from an infinitely distant source at the top of the atmosphere
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct DirectionalLight {
+    /// Unit vector pointing toward the light
+    pub dir: Vec3,
+    pub radiance: Vec3,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Sun {
     pos: Vec4,
     pub a: f32,
     pub z: f32,
     pub d: f32,
+    pub color: Color32,
+    pub intensity: f32,
+    /// Apparent diameter of the disk in degrees
+    pub angular_diameter: f32,
+}
+
+impl Default for Sun {
+    fn default() -> Self {
+        Self::new(1.0, -90.0, 0.0)
+    }
 }
 
 impl Sun {
     pub fn new(d: f32, a: f32, z: f32) -> Self {
         let pos = Vec4::new(-1.0, 0.0, 0.0, 0.0);
-        Self { pos, a, z, d }
+        Self {
+            pos,
+            a,
+            z,
+            d,
+            color: Color32::WHITE,
+            intensity: 1.0,
+            angular_diameter: 0.53,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_angular_diameter(mut self, angular_diameter: f32) -> Self {
+        self.angular_diameter = angular_diameter;
+        self
+    }
+
+    /// Unit vector pointing toward the sun
+    #[inline]
+    pub fn dir(&self) -> Vec3 {
+        self.get_pos().normalize()
+    }
+
+    /// Angular radius of the disk in radians
+    #[inline]
+    pub fn angular_radius(&self) -> f32 {
+        (self.angular_diameter * 0.5).to_radians()
+    }
+
+    pub fn light(&self) -> DirectionalLight {
+        DirectionalLight {
+            dir: self.dir(),
+            radiance: color::to_linear_rgb(self.color) * self.intensity,
+        }
+    }
+
+    /// Relative brightness of the disk at `r`, the distance from the centre as a fraction of the radius
+    #[inline]
+    pub fn limb_darkening(r: f32) -> f32 {
+        const U: f32 = 0.6;
+        let mu = (1.0 - r * r).max(0.0).sqrt();
+        1.0 - U * (1.0 - mu)
     }
 
     #[inline]
     pub fn get_pos(&self) -> Vec3 {
-        let mat = glam::Mat4::from_rotation_y(self.z.to_radians()) * glam::Mat4::from_rotation_z(self.a.to_radians())
+        let mat = glam::Mat4::from_rotation_y(self.z.to_radians())
+            * glam::Mat4::from_rotation_z(self.a.to_radians())
             * glam::Mat4::from_scale(Vec3::splat(self.d));
         (mat * self.pos).xyz()
     }
@@ -27,11 +101,32 @@ impl Sun {
         self.a = a.x;
         self.z = a.y;
     }
-    
+
+    /// Places the sun by elevation and azimuth. Azimuth 0 points along -x, 90 along +z
+    pub fn set_solar_position(&mut self, position: SolarPosition) {
+        self.a = -position.elevation;
+        self.z = position.azimuth;
+    }
+
+    pub fn set_time_of_day(&mut self, time: &TimeOfDay) {
+        self.set_solar_position(time.solar_position());
+    }
 
     pub fn set_d(&mut self, d: f32) {
         self.d = d
     }
+
+    pub fn set_color(&mut self, color: Color32) {
+        self.color = color
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity
+    }
+
+    pub fn set_angular_diameter(&mut self, angular_diameter: f32) {
+        self.angular_diameter = angular_diameter
+    }
 }
 
 impl Visitable for Sun {
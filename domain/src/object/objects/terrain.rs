@@ -3,6 +3,7 @@
 use egui::Color32;
 use glam::Vec3;
 use rayon::iter::IntoParallelIterator;
+use serde::{Deserialize, Serialize};
 use std::ops::{Deref, DerefMut};
 
 use crate::object::objects::texture3d::{INoise, INoiseBuilder, Noise, NoiseBuilder, Perlin, PerlinBuilder};
@@ -10,7 +11,8 @@ use crate::object::objects::textures::texture3d::{Worley, WorleyBuilder};
 use crate::object::objects::BoundingBox;
 use crate::visitor::{Visitable, Visitor};
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TerrainBuilder {
     pub bounding_box: BoundingBox,
     pub scale: usize,
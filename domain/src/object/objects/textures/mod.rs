@@ -1,3 +1,4 @@
+pub mod slice;
 pub mod texture2d;
 pub mod texture3d;
 
@@ -0,0 +1,125 @@
+//! Planar slices of the 3D noise textures for inspecting them in the app
+
+use glam::Vec3;
+
+use super::texture3d::{INoise, Noise};
+
+/// Bins of the channel histogram over `[0, 1]`
+pub const HISTOGRAM_BINS: usize = 32;
+
+/// Texture of a cloud
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CloudTexture {
+    #[default]
+    Shape,
+    Detail,
+    Weather,
+}
+
+impl CloudTexture {
+    pub const ALL: [CloudTexture; 3] = [Self::Shape, Self::Detail, Self::Weather];
+}
+
+/// Plane of the slice, the remaining axis is the slice depth
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SlicePlane {
+    #[default]
+    XY,
+    XZ,
+    YZ,
+}
+
+impl SlicePlane {
+    pub const ALL: [SlicePlane; 3] = [Self::XY, Self::XZ, Self::YZ];
+
+    /// Texture coordinates of the point `u`, `v` of the plane at `depth`
+    #[inline]
+    fn uvw(self, u: f32, v: f32, depth: f32) -> Vec3 {
+        match self {
+            Self::XY => Vec3::new(u, v, depth),
+            Self::XZ => Vec3::new(u, depth, v),
+            Self::YZ => Vec3::new(depth, u, v),
+        }
+    }
+}
+
+/// Which slice of which texture channel to take
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct NoiseSlice {
+    pub texture: CloudTexture,
+    pub plane: SlicePlane,
+    /// Position of the slice along the remaining axis in `[0, 1]`
+    pub depth: f32,
+    /// 0 to 3 for the R, G, B and A channels
+    pub channel: usize,
+}
+
+/// Channel values of a slice, one per texel, with their histogram
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceImage {
+    pub size: usize,
+    /// Row-major values, `v` grows downwards
+    pub values: Vec<f32>,
+    /// Texel counts per bin, values outside `[0, 1]` fall into the end bins
+    pub histogram: [u32; HISTOGRAM_BINS],
+}
+
+impl NoiseSlice {
+    /// Samples the slice at the texture resolution, one value per texel
+    pub fn sample(&self, noise: &Noise) -> SliceImage {
+        let size = noise.resolution().max(1);
+        let layer = ((self.depth.clamp(0.0, 1.0) * size as f32) as usize).min(size - 1);
+        let texel = |i: usize| (i as f32 + 0.5) / size as f32;
+        let values = (0..size * size)
+            .map(|i| {
+                let uvw = self
+                    .plane
+                    .uvw(texel(i % size), texel(i / size), texel(layer));
+                noise.sample_level(uvw)[self.channel.min(3)]
+            })
+            .collect::<Vec<_>>();
+
+        let mut histogram = [0; HISTOGRAM_BINS];
+        for &value in &values {
+            let bin = (value.clamp(0.0, 1.0) * HISTOGRAM_BINS as f32) as usize;
+            histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+        SliceImage {
+            size,
+            values,
+            histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::texture3d::{INoiseBuilder, WorleyBuilder};
+
+    #[test]
+    fn test_slice_histogram() {
+        let noise = Noise::Worley(
+            WorleyBuilder::new()
+                .with_seed(0)
+                .with_resolution(8)
+                .with_num_points_a(2)
+                .with_num_points_b(3)
+                .with_num_points_c(4)
+                .with_tile(1.0)
+                .build(),
+        );
+        let slice = NoiseSlice {
+            plane: SlicePlane::XZ,
+            depth: 0.5,
+            ..Default::default()
+        };
+        let img = slice.sample(&noise);
+
+        assert_eq!(img.size, 8);
+        assert_eq!(img.values.len(), 64);
+        assert_eq!(img.histogram.iter().sum::<u32>(), 64);
+        let uvw = Vec3::new(1.5 / 8.0, 4.5 / 8.0, 2.5 / 8.0);
+        assert_eq!(img.values[2 * 8 + 1], noise.sample_level(uvw).x);
+    }
+}
@@ -3,6 +3,7 @@ use std::ops::{Deref, DerefMut, Index, IndexMut};
 use glam::{IVec3, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 const OFFSETS: [IVec3; 27] = [
     // centre
@@ -75,7 +76,8 @@ impl<T> IndexMut<UVec3> for Texture3D<T> {
     }
 }
 
-#[derive(Default, Debug, PartialEq, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WorleyBuilder {
     pub seed: u64,
     pub num_points_a: usize,
@@ -213,7 +215,8 @@ impl Worley {
 // pub type Perlin = Worley;
 // pub type PerlinBuilder = WorleyBuilder;
 
-#[derive(Default, Debug, PartialEq, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PerlinBuilder {
     pub seed: u64,
     pub num_points_a: usize,
@@ -381,6 +384,16 @@ impl Default for Noise {
     }
 }
 
+impl Noise {
+    /// Texels along each side of the texture
+    pub fn resolution(&self) -> usize {
+        match self {
+            Noise::Worley(x) => x.builder.resolution,
+            Noise::Perlin(x) => x.builder.resolution,
+        }
+    }
+}
+
 impl INoise for Noise {
     type NoiseBuilder = NoiseBuilder;
     fn sample_level(&self, vec3: Vec3) -> Vec4 {
@@ -401,7 +414,7 @@ impl INoise for Noise {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NoiseBuilder {
     WorleyBuilder(WorleyBuilder),
     PerlinBuilder(PerlinBuilder),
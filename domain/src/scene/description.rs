@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::Camera;
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::sky::SkyBuilder;
+use crate::object::objects::terrain::TerrainBuilder;
+use crate::object::objects::{Grid, Moon, Sun};
+use crate::object::Component;
+
+/// Scene object stored by its parameters, the noise is generated on build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ObjectDescription {
+    Cloud(Box<CloudBuilder>),
+    Terrain(TerrainBuilder),
+    Sky(SkyBuilder),
+    Sun(Sun),
+    Moon(Moon),
+    Grid(Grid),
+}
+
+impl ObjectDescription {
+    pub fn build(self) -> Component {
+        match self {
+            ObjectDescription::Cloud(x) => x.build().into(),
+            ObjectDescription::Terrain(x) => x.build().into(),
+            ObjectDescription::Sky(x) => x.build().into(),
+            ObjectDescription::Sun(x) => x.into(),
+            ObjectDescription::Moon(x) => x.into(),
+            ObjectDescription::Grid(x) => x.into(),
+        }
+    }
+}
+
+/// Serializable list of named scene objects and camera bookmarks
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneDescription {
+    pub objects: BTreeMap<String, ObjectDescription>,
+    /// Named viewpoints for comparing renders of the scene
+    pub bookmarks: BTreeMap<String, Camera>,
+}
+
+impl SceneDescription {
+    pub fn with_object(mut self, name: impl Into<String>, object: ObjectDescription) -> Self {
+        self.objects.insert(name.into(), object);
+        self
+    }
+
+    pub fn with_bookmark(mut self, name: impl Into<String>, camera: Camera) -> Self {
+        self.bookmarks.insert(name.into(), camera);
+        self
+    }
+
+    /// Builds every object. Scene names are `&'static str`, so the names are leaked,
+    /// which is fine for the handful of objects loaded once per process.
+    pub fn build(self) -> impl Iterator<Item = (&'static str, Component)> {
+        self.objects
+            .into_iter()
+            .map(|(name, object)| (&*name.leak(), object.build()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks_survive_writing_the_scene_back() {
+        let json = include_str!("../../../render/scenes/scene.json");
+        let scene: SceneDescription = serde_json::from_str(json).unwrap();
+        assert!(scene.bookmarks.contains_key("overview"));
+
+        let written = serde_json::to_string_pretty(&scene).unwrap();
+        let reread: SceneDescription = serde_json::from_str(&written).unwrap();
+        assert_eq!(reread.bookmarks, scene.bookmarks);
+        assert_eq!(serde_json::to_string_pretty(&reread).unwrap(), written);
+    }
+}
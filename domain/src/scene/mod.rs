@@ -1,3 +1,4 @@
+pub mod description;
 #[allow(clippy::module_inception)]
 pub mod scene;
 pub mod scene_composite;
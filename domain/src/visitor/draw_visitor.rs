@@ -1,18 +1,24 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::ops::Sub;
-use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use egui::{Color32, Pos2, Stroke, TextureId};
-use glam::{Vec3, Vec4, Vec4Swizzles};
+use egui::{Color32, Stroke, TextureId};
+use glam::{Vec3, Vec4};
 use log::debug;
 
 use crate::canvas::painter::Painter3D;
+use crate::canvas::{Framebuffer, ToneMapping};
 use crate::math::Transform;
 use crate::object::camera::Camera;
-use crate::object::objects::{BoundingBox, Cloud, Grid, Sun, Terrain};
-use crate::object::objects::cloud::{beer, hg, phase};
+use crate::object::objects::{BoundingBox, Cloud, Grid, Moon, Sky, Sun, Terrain};
+use crate::object::Component;
 use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::progressive::{CloudFrameKey, ProgressiveCloud};
+use crate::visitor::sampling::Supersampling;
+use crate::visitor::shading::{
+    box_bounds, disk_bounds, fill_depth, shade_pixels, shade_region, Lighting, PixelRays,
+};
+use crate::visitor::stats::{PassCounters, RenderStats};
+use crate::visitor::temporal::{CloudFrame, CloudHistoryKey, TemporalCloud};
 use crate::visitor::{Visitable, Visitor};
 
 pub struct DrawVisitor<'a> {
@@ -20,43 +26,213 @@ pub struct DrawVisitor<'a> {
     camera: &'a Camera,
     stroke: Stroke,
     mvp: Transform,
+    width: usize,
+    height: usize,
+    lighting: Lighting,
+    tone_mapping: ToneMapping,
+    supersampling: Supersampling,
+    cloud_debug: CloudDebugView,
+    terrain_debug: TerrainDebug,
+    /// Terrain and clouds composited by depth before they are painted
+    layer: Framebuffer,
+    /// Cloud frames kept between draws, in the order the clouds are visited
+    clouds: CloudCache<'a>,
+    cloud_index: usize,
+    stats: RenderStats,
+    counters: PassCounters,
 }
 
+/// How clouds are rendered on screen
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CloudRenderMode {
+    /// Every pixel is marched on every draw
+    Full,
+    /// Refined tile by tile while the view and the parameters stay the same
+    #[default]
+    Progressive,
+    /// One pixel in every 4x4 block is marched per draw and accumulated into the history,
+    /// the rest is reprojected
+    Temporal,
+}
+
+impl CloudRenderMode {
+    pub const ALL: [CloudRenderMode; 3] = [Self::Full, Self::Progressive, Self::Temporal];
+}
+
+/// False colour views of the cloud marcher for tuning its parameters
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CloudDebugView {
+    /// The cloud is shaded normally
+    #[default]
+    Off,
+    /// Highest density along the ray, blue where every sample is empty
+    Density,
+    /// Fraction of the background seen through the cloud
+    Transmittance,
+    /// Transmittance towards the light averaged over the lit samples
+    LightTransmittance,
+    /// Phase function towards the eye, heat mapped
+    Phase,
+    /// Density samples taken by the ray and its light marches, heat mapped
+    Steps,
+    /// Weather map projected on the ground under the cloud
+    WeatherMap,
+}
+
+impl CloudDebugView {
+    pub const ALL: [CloudDebugView; 7] = [
+        Self::Off,
+        Self::Density,
+        Self::Transmittance,
+        Self::LightTransmittance,
+        Self::Phase,
+        Self::Steps,
+        Self::WeatherMap,
+    ];
+}
+
+/// Overlays drawn over the terrain to check its mesh
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TerrainDebug {
+    pub wireframe: bool,
+    /// Vertex normals as short lines
+    pub normals: bool,
+    pub bounding_box: bool,
+}
+
+enum CloudCache<'a> {
+    None,
+    Progressive(&'a mut Vec<ProgressiveCloud>, Duration),
+    Temporal(&'a mut Vec<TemporalCloud>),
+}
+
+/// The sky is rendered at a fraction of the canvas resolution and stretched
+const SKY_DOWNSAMPLE: usize = 4;
+
 impl<'a> DrawVisitor<'a> {
     pub fn new(camera: &'a Camera, canvas: &'a Painter3D) -> Self {
-        let resp_rect = canvas.resp_rect().sub((-8.0).into());
-        let proj = camera.projection(resp_rect.width(), resp_rect.height());
+        let rect = canvas.resp_rect();
+        let proj = camera.projection(rect.width(), rect.height());
         let camera_tf = proj * camera.view();
 
-        let res = Self {
+        Self {
             canvas,
             camera,
             stroke: Stroke::new(1.0, Color32::GRAY),
-            mvp: Transform::new(camera_tf, resp_rect),
-        };
-        res.visit_sky();
-        res
+            mvp: Transform::new(camera_tf, rect),
+            width: rect.width() as usize,
+            height: rect.height() as usize,
+            lighting: Lighting::default(),
+            tone_mapping: ToneMapping::default(),
+            supersampling: Supersampling::default(),
+            cloud_debug: CloudDebugView::default(),
+            terrain_debug: TerrainDebug::default(),
+            layer: Framebuffer::default(),
+            clouds: CloudCache::None,
+            cloud_index: 0,
+            stats: RenderStats::default(),
+            counters: PassCounters::default(),
+        }
     }
 
     pub fn with_stroke(mut self, stroke: Stroke) -> Self {
         self.stroke = stroke;
         self
     }
+
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    pub fn with_supersampling(mut self, supersampling: Supersampling) -> Self {
+        self.supersampling = supersampling;
+        self
+    }
+
+    /// Draws clouds in a false colour view instead of shading them
+    pub fn with_cloud_debug(mut self, view: CloudDebugView) -> Self {
+        self.cloud_debug = view;
+        self
+    }
+
+    pub fn with_terrain_debug(mut self, debug: TerrainDebug) -> Self {
+        self.terrain_debug = debug;
+        self
+    }
+
+    /// Renders clouds progressively into `clouds`, spending about `budget` on them per draw
+    pub fn with_progressive_clouds(
+        mut self,
+        clouds: &'a mut Vec<ProgressiveCloud>,
+        budget: Duration,
+    ) -> Self {
+        self.clouds = CloudCache::Progressive(clouds, budget);
+        self
+    }
+
+    /// Reprojects clouds from the frames kept in `clouds`
+    pub fn with_temporal_clouds(mut self, clouds: &'a mut Vec<TemporalCloud>) -> Self {
+        self.clouds = CloudCache::Temporal(clouds);
+        self
+    }
+
+    /// Timings and counters of the last visit of the scene
+    pub fn stats(&self) -> &RenderStats {
+        &self.stats
+    }
 }
 
 impl<'a> Visitor for DrawVisitor<'a> {
     fn visit_composite(&mut self, scene_objects: &SceneObjects) {
-        let mut objs = scene_objects.values().collect::<Vec<_>>();
+        let start = Instant::now();
+        self.stats = RenderStats::default();
+        self.counters = PassCounters::default();
+        self.lighting = Lighting::gather(scene_objects);
+        self.cloud_index = 0;
+        if let Some(sky) = self.lighting.sky {
+            self.visit_sky(&sky);
+        }
+
+        let mut objs = scene_objects
+            .values()
+            .filter(|x| !matches!(x, Component::Sky(_)))
+            .collect::<Vec<_>>();
         objs.sort_by(|x, y| {
             (y.pos() - self.camera.pos())
                 .length()
                 .partial_cmp(&(x.pos() - self.camera.pos()).length())
                 .unwrap_or(Ordering::Greater)
         });
-        
-        for i in objs {
+
+        // Terrain goes first so that its depth clips the cloud rays
+        let (volumes, rest): (Vec<_>, Vec<_>) = objs
+            .into_iter()
+            .partition(|x| matches!(x, Component::Terrain(_) | Component::Cloud(_)));
+        if !volumes.is_empty() {
+            self.layer = Framebuffer::new(self.width, self.height);
+            let (terrains, clouds): (Vec<_>, Vec<_>) = volumes
+                .into_iter()
+                .partition(|x| matches!(x, Component::Terrain(_)));
+            for &i in terrains.iter().chain(&clouds) {
+                i.accept(self);
+            }
+            let layer = std::mem::take(&mut self.layer);
+            self.blit("scene", &layer, [0, 0], egui::TextureOptions::NEAREST);
+
+            // Painted over the layer, the raster passes would hide them otherwise
+            for i in terrains {
+                if let Component::Terrain(terrain) = i {
+                    self.draw_terrain_debug(terrain);
+                }
+            }
+        }
+
+        for i in rest {
             i.accept(self);
         }
+        self.stats.record(&self.counters);
+        self.stats.total = start.elapsed();
     }
 
     fn visit_camera(&mut self, _camera: &Camera) {
@@ -64,127 +240,9 @@ impl<'a> Visitor for DrawVisitor<'a> {
     }
 
     fn visit_cloud(&mut self, cloud: &Cloud) {
-        self.canvas
-            .ctx()
-            .data_mut(|x| x.insert_temp("cloud".into(), cloud.clone()));
-        use rayon::prelude::*;
-
-        let bb = cloud.bounding_box();
-        let corners = bb.corners();
-
-        let (width, height) = (1056.0, 900.0);
-
-        let (min_tuple, max_tuple) = corners
-            .iter()
-            .enumerate()
-            .map(|(i, &corner)| {
-                self.canvas.transform(corner, self.mvp).unwrap_or_else(|| {
-                    if i < 4 {
-                        Pos2::new(width, height)
-                    } else {
-                        Pos2::ZERO
-                    }
-                })
-            })
-            .fold(
-                (Pos2::new(width, height), Pos2::new(0.0, 0.0)),
-                |(mut min, mut max), p| {
-                    min.x = min.x.min(p.x).clamp(0.0, width);
-                    min.y = min.y.min(p.y).clamp(0.0, height);
-                    max.x = max.x.max(p.x).clamp(0.0, width);
-                    max.y = max.y.max(p.y).clamp(0.0, height);
-                    (min, max)
-                },
-            );
-
-        let wh = max_tuple - min_tuple;
-        let (w, h) = (wh.x as usize, wh.y as usize);
-
-        let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
-        let light_color: Vec4 = cloud
-            .light_color
-            .to_array()
-            .map(|x| x as f32 / 255.0)
-            .into();
-
-        let sun = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_persisted::<Sun>("sun".into()));
-        let sun = sun.unwrap_or_default();
-        let sun_pos = sun.get_pos();
-        let ray_origin = self.camera.pos();
-        img.pixels
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(idx, pixel)| {
-                let i = idx / w + min_tuple.y as usize;
-                let j = idx % w + min_tuple.x as usize;
-
-                let ray_dir = (self.camera.egui_to_world(i, j, 1056, 900) - ray_origin).normalize();
-
-                let ray_box_info = bb.dst(ray_origin, ray_dir);
-                let dst_to_box = ray_box_info.x;
-                let dst_inside_box = ray_box_info.y;
-
-                if dst_inside_box <= 0.0 {
-                    *pixel = Color32::TRANSPARENT
-                } else {
-                    let mut dst_travelled = 0.0;
-                    let dst_limit = dst_inside_box;
-                    let step_size = dst_inside_box / cloud.num_steps as f32;
-                    let mut transmittance = 1.0;
-                    let mut light_energy = 0.0;
-
-                    let entry_point = ray_origin + dst_to_box * ray_dir;
-                    let cos_angle = ray_dir.dot((sun_pos).normalize());
-                    let phase = phase(cos_angle, cloud.phase_params);
-
-                    while dst_travelled < dst_limit {
-                        let ray_pos = entry_point + ray_dir * dst_travelled;
-                        let density = cloud.sample_density(ray_pos);
-                        if density > 0.1 {
-                            let light_transmittance = cloud.light_march(ray_pos, sun_pos);
-                            light_energy +=
-                                density * step_size * transmittance * light_transmittance * phase;
-                            transmittance *=
-                                beer(density * step_size * cloud.light_absorption_through_cloud);
-                            if transmittance < 0.01 {
-                                break;
-                            }
-                        }
-                        dst_travelled += step_size;
-                    }
-
-                    let focused_eye_cos = cos_angle.clamp(-1.0, 1.0).powf(cloud.params.x);
-                    let sun =
-                        hg(focused_eye_cos, cloud.phase_params.w).clamp(-1.0, 1.0) * transmittance;
-
-                    let cloud_col = light_energy * light_color.xyz();
-                    let col = cloud_col.clamp(Vec3::ZERO, Vec3::ONE) * (1.0 - sun)
-                        + light_color.xyz() * sun;
-                    let (r, g, b) = col.into();
-                    *pixel = Color32::from_rgba_unmultiplied(
-                        (r * 255.0) as u8,
-                        (g * 255.0) as u8,
-                        (b * 255.0) as u8,
-                        (255.0 * (1.0 - transmittance)) as u8,
-                    );
-                }
-            });
-
-        let handle = self
-            .canvas
-            .ctx()
-            .load_texture("cloud", img, Default::default());
-        let textureid = TextureId::from(&handle);
-        self.canvas.image(
-            textureid,
-            egui::Rect::from_two_pos(min_tuple, max_tuple),
-            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-            Color32::WHITE,
-        );
-        // self.visit_bounding_box(bb);
+        let start = Instant::now();
+        self.draw_cloud(cloud);
+        self.stats.clouds.push(start.elapsed());
     }
 
     fn visit_grid(&mut self, grid: &Grid) {
@@ -298,10 +356,11 @@ impl<'a> Visitor for DrawVisitor<'a> {
     }
 
     fn visit_sun(&mut self, sun: &Sun) {
+        // With a sky present the disk is part of the sky pass
+        if self.lighting.sky.is_some() {
+            return;
+        }
         let sun_pos = sun.get_pos();
-        self.canvas
-            .ctx()
-            .data_mut(|x| x.insert_persisted("sun".into(), *sun));
         self.canvas.circle_filled(
             sun_pos,
             sun.get_pos() + Vec3::new(0.1, 0.0, 0.0),
@@ -310,258 +369,286 @@ impl<'a> Visitor for DrawVisitor<'a> {
         );
     }
 
-    fn visit_terrain(&mut self, terrain: &Terrain) {
-        use rayon::prelude::*;
-
-        let sun = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_persisted::<Sun>("sun".into()));
+    fn visit_moon(&mut self, moon: &Moon) {
+        if self.lighting.sky.is_some() {
+            return;
+        }
+        let moon_pos = moon.dir() * self.lighting.sun.map_or(10.0, |sun| sun.d);
+        self.canvas.circle_filled(
+            moon_pos,
+            moon_pos + Vec3::new(0.1, 0.0, 0.0),
+            Color32::from_gray(220),
+            self.mvp,
+        );
+    }
 
-        if sun.is_none() {
+    fn visit_sky(&mut self, sky: &Sky) {
+        if !self.lighting.is_lit() {
             return;
         }
-        let sun_pos = sun.unwrap().get_pos();
-        let cloud = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_temp::<Cloud>("cloud".into()))
-            .unwrap_or_default();
-
-        let (width, height) = (1056.0, 900.0);
-        let (min_tuple, max_tuple) = (Pos2::ZERO, Pos2::new(width, height));
-        let wh = max_tuple - min_tuple;
-        let (w, h) = (wh.x as usize, wh.y as usize);
-        let bb = terrain.bounding_box;
-
-        let img = Arc::new(Mutex::new(egui::ColorImage::new(
-            [w, h],
-            Color32::TRANSPARENT,
-        )));
-        let z_buffer = Arc::new(Mutex::new(HashMap::new()));
-
-        terrain.triangles.par_iter().for_each(|(v, (n0, n1, n2))| {
-            let img = img.clone();
-            let z_buffer = z_buffer.clone();
-            let get_shadow_factor = |probe: Vec3| -> f32 {
-                let sun_dir = (sun_pos - probe).normalize();
-                let cloud_bb = cloud.bounding_box().dst(probe, sun_dir);
-                let (dir_to_box, dst_inside_box) = cloud_bb.into();
-                if dst_inside_box != 0.0 {
-                    let mut p = probe;
-                    let num_steps = terrain.num_shadows_steps;
-                    let step_size = dst_inside_box / num_steps as f32;
-                    p += dir_to_box * sun_dir;
-
-                    let mut total_density = 0.0;
-
-                    for _ in 0..num_steps {
-                        let density = cloud.sample_density(p);
-                        total_density += density.max(0.0) * step_size;
-                        p += sun_dir * step_size;
-                    }
-                    beer(total_density / terrain.density_scale).clamp(terrain.shadow_threshold, 1.0)
-                } else {
-                    1.0
-                }
-            };
+        let (start, upload) = (Instant::now(), self.counters.upload_time());
+        let height = self.camera.pos().y;
 
-            let (v0, v1, v2) = v.to_tuple();
-            let (p0, p1, p2) = (v0, v1, v2);
-
-            let v0 = self.canvas.transform(v0, self.mvp);
-            let v1 = self.canvas.transform(v1, self.mvp);
-            let v2 = self.canvas.transform(v2, self.mvp);
-            if let (Some(v0), Some(v1), Some(v2)) = (v0, v1, v2) {
-                let min_x = v0.x.min(v1.x).min(v2.x) as usize;
-                let max_x = v0.x.max(v1.x).max(v2.x) as usize;
-                let min_y = v0.y.min(v1.y).min(v2.y) as usize;
-                let max_y = v0.y.max(v1.y).max(v2.y) as usize;
-
-                for y in min_y..=max_y {
-                    for x in min_x..=max_x {
-                        if inside_triangle(Pos2::new(x as f32, y as f32), v0, v1, v2)
-                            && x < width as usize
-                            && y < height as usize
-                        {
-                            let a1 = Vec3::new(p1.x - p0.x, p1.y - p0.y, p1.z - p0.z);
-                            let a2 = Vec3::new(p2.x - p0.x, p2.y - p0.y, p2.z - p0.z);
-                            let normal = a1.cross(a2);
-                            let d = -(normal.x * p0.x + normal.y * p0.y + normal.z * p0.z);
-                            let (a, b, c, d) = (normal.x, normal.y, normal.z, d);
-
-                            let new_u = p1.x + (p0.x - p1.x) * ((x as f32 - v1.x) / (v0.x - v1.x));
-                            let new_v = p1.y + (p0.y - p1.y) * ((y as f32 - v1.y) / (v0.y - v1.y));
-                            let z_pixel = if c != 0.0 {
-                                -(a * new_u + b * new_v + d) / c
-                            } else {
-                                0.0
-                            };
-
-                            let probe = Vec3::new(new_u, new_v, z_pixel);
-
-                            let depth = self.camera.pos().distance_squared(probe);
-
-                            let x1 = get_shadow_factor(p0);
-                            let x2 = get_shadow_factor(p1);
-                            let x3 = get_shadow_factor(p2);
-
-                            let alpha1 = ((sun_pos - p0).normalize()).dot(n0.normalize());
-                            let alpha2 = ((sun_pos - p1).normalize()).dot(n1.normalize());
-                            let alpha3 = ((sun_pos - p2).normalize()).dot(n2.normalize());
-
-                            let beta = interpolate(
-                                Pos2::new(x as f32, y as f32),
-                                v0,
-                                v1,
-                                v2,
-                                x1 * alpha1,
-                                x2 * alpha2,
-                                x3 * alpha3,
-                            );
-
-                            let dif = 0.55 * beta;
-                            let bottom = color32_to_vec4(terrain.bottom_color).xyz();
-                            let top = color32_to_vec4(terrain.top_color).xyz();
-                            let p0_col = bottom.lerp(top, (p0.y - bb.min.y).abs() / bb.size().y);
-                            let p1_col = bottom.lerp(top, (p1.y - bb.min.y).abs() / bb.size().y);
-                            let p2_col = bottom.lerp(top, (p2.y - bb.min.y).abs() / bb.size().y);
-
-                            let col = interpolate(
-                                Pos2::new(x as f32, y as f32),
-                                v0,
-                                v1,
-                                v2,
-                                p0_col,
-                                p1_col,
-                                p2_col,
-                            );
-                            let col = col * dif;
-                            let (r, g, b) = (col.x * 255.0, col.y * 255.0, col.z * 255.0);
-                            let color = Color32::from_rgb(r as u8, g as u8, b as u8);
-                            let mut z_buffer = z_buffer.lock().unwrap();
-                            if let Some(existing_depth) = z_buffer.get(&(x, y)) {
-                                if depth < *existing_depth {
-                                    img.lock().unwrap()[(x, y)] = color;
-                                    z_buffer.insert((x, y), depth);
-                                }
-                            } else {
-                                img.lock().unwrap()[(x, y)] = color;
-                                z_buffer.insert((x, y), depth);
-                            }
-                        }
-                    }
-                }
-            }
+        let (w, h) = (self.width / SKY_DOWNSAMPLE, self.height / SKY_DOWNSAMPLE);
+        let img = shade_region(self.camera, w, h, [0, 0], [w, h], |ray_dir| {
+            Some(self.lighting.sky_pixel(sky, height, ray_dir))
         });
-
-        let img = Arc::try_unwrap(img)
-            .expect("one strong reference")
-            .into_inner()
-            .expect("No one holding the mutex");
-        let handle = self
-            .canvas
-            .ctx()
-            .load_texture("terrain", img, egui::TextureOptions::NEAREST);
-        let textureid = TextureId::from(&handle);
+        let upload_start = Instant::now();
+        let handle = self.canvas.ctx().load_texture(
+            "sky",
+            img.to_color_image(&self.tone_mapping),
+            Default::default(),
+        );
+        self.counters.upload(upload_start.elapsed());
         self.canvas.image(
-            textureid,
-            egui::Rect::from_two_pos(min_tuple, max_tuple),
+            TextureId::from(&handle),
+            self.canvas.resp_rect(),
             egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
             Color32::WHITE,
         );
 
-        // self.visit_bounding_box(&terrain.bounding_box);
+        self.draw_stars(sky);
+        if let Some(moon) = self.lighting.moon {
+            let shade = Lighting::moon_disk(sky, &moon, height);
+            self.draw_disk("moon", moon.dir(), moon.angular_radius(), shade);
+        }
+        if let Some(sun) = self.lighting.sun {
+            let shade = Lighting::sun_disk(sky, &sun, height);
+            self.draw_disk("sun", sun.dir(), sun.angular_radius(), shade);
+        }
+        self.stats.sky += start.elapsed() - (self.counters.upload_time() - upload);
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) {
+        if !self.lighting.is_lit() {
+            return;
+        }
+
+        let start = Instant::now();
+        let img = self.lighting.rasterize_terrain(
+            terrain,
+            self.camera,
+            self.width,
+            self.height,
+            &self.supersampling.offsets(),
+            Some(&self.counters),
+        );
+        self.layer.blend(&img, [0, 0]);
+        self.stats.terrain += start.elapsed();
     }
 }
 
 impl<'a> DrawVisitor<'a> {
-    fn visit_sky(&self) {
-        use rayon::prelude::*;
-        let (width, height) = (1066.0, 950.0);
-        let (min_tuple, max_tuple) = (Pos2::ZERO, Pos2::new(width, height));
-        let wh = max_tuple - min_tuple;
-        let (w, h) = (wh.x as usize, wh.y as usize);
-
-        let sun = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_persisted::<Sun>("sun".into()));
-        if sun.is_none() {
+    /// Ray marches the cloud into the layer, reusing the cached frames of the render mode
+    fn draw_cloud(&mut self, cloud: &Cloud) {
+        if self.cloud_debug != CloudDebugView::Off {
+            self.draw_cloud_debug(cloud);
             return;
         }
-        let sun = sun.unwrap();
-
-        let mut img = egui::ColorImage::new([w, h], Color32::BLACK);
-        img.pixels
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(idx, pixel)| {
-                let i = idx / w + min_tuple.y as usize;
-                let j = idx % w + min_tuple.x as usize;
-                let uv = glam::Vec2::new(j as f32, 900.0 - i as f32) / 900.0;
-
-                let atmosphere = (1.0_f32 - uv.y).sqrt();
-                let sky_col = Vec3::new(0.2, 0.4, 0.8);
-
-                let scatter = sun.get_pos().y / sun.d;
-                let scatter = scatter.powf(1.0 / 10.0);
-                let scatter = 1.0 - scatter.clamp(0.2, 0.9);
-
-                let col = Vec3::splat(1.0).lerp(Vec3::new(0.8, 0.3, 0.0) * 1.1, scatter);
-                let col = sky_col.lerp(col, atmosphere / 1.1);
-                let (r, g, b) = col.into();
-                *pixel = Color32::from_rgba_unmultiplied(
-                    (r * 255.0) as u8,
-                    (g * 255.0) as u8,
-                    (b * 255.0) as u8,
-                    255,
+        let Some((min, max)) =
+            box_bounds(self.camera, cloud.bounding_box(), self.width, self.height)
+        else {
+            return;
+        };
+        let shader = self
+            .lighting
+            .cloud_shader(cloud, self.camera.pos())
+            .with_counters(&self.counters);
+
+        let index = self.cloud_index;
+        self.cloud_index += 1;
+        let view_proj = self
+            .camera
+            .projection(self.width as f32, self.height as f32)
+            * self.camera.view();
+        let rays = PixelRays::new(self.camera, self.width, self.height);
+
+        let (layer, supersampling) = (&self.layer, &self.supersampling);
+        let shade =
+            |x: usize, y: usize| shader.shade_pixel(&rays, x, y, supersampling, layer.depth(x, y));
+
+        // Taken out for the draw, so that the finished frame can be blended through `self`
+        let mut img = match std::mem::replace(&mut self.clouds, CloudCache::None) {
+            CloudCache::None => shade_pixels(min, max, |x, y| Some((shade(x, y), f32::INFINITY))),
+            CloudCache::Progressive(clouds, budget) => {
+                if clouds.len() <= index {
+                    clouds.resize_with(index + 1, Default::default);
+                }
+                let key = CloudFrameKey {
+                    view_proj,
+                    projection: self.camera.kind,
+                    physical: self.camera.physical,
+                    min,
+                    max,
+                    cloud: **cloud,
+                    sun: self.lighting.sun,
+                    moon: self.lighting.moon,
+                    sky: self.lighting.sky,
+                };
+                let img = clouds[index].render(key, budget, shade).clone();
+                self.clouds = CloudCache::Progressive(clouds, budget);
+                img
+            }
+            CloudCache::Temporal(clouds) => {
+                if clouds.len() <= index {
+                    clouds.resize_with(index + 1, Default::default);
+                }
+                let lighting = &self.lighting;
+                let key = CloudHistoryKey::new(
+                    self.width,
+                    self.height,
+                    self.camera.physical,
+                    **cloud,
+                    lighting.sun,
+                    lighting.moon,
+                    lighting.sky,
                 );
-            });
+                let frame = CloudFrame {
+                    camera: *self.camera,
+                    offset: cloud.offset,
+                };
+                let bb = *cloud.bounding_box();
+                let history = clouds[index].render(
+                    key,
+                    frame,
+                    min,
+                    max,
+                    |offset| cloud.drift(offset),
+                    |x, y| {
+                        let (ray_origin, ray_dir) = rays.pixel(x, y);
+                        let dst = bb.dst(ray_origin, ray_dir);
+                        (dst.y > 0.0).then(|| ray_origin + ray_dir * (dst.x + dst.y * 0.5))
+                    },
+                    |x, y| layer.depth(x, y),
+                    shade,
+                );
+                let img = history.crop(min, max);
+                self.clouds = CloudCache::Temporal(clouds);
+                img
+            }
+        };
+        fill_depth(&mut img, min, |x, y| {
+            let (ray_origin, ray_dir) = rays.pixel(x, y);
+            shader.entry(ray_origin, ray_dir)
+        });
+        self.stats.covered_pixels += img.pixels().iter().filter(|col| col.w > 0.0).count() as u64;
+        self.layer.blend(&img, min);
+        // self.visit_bounding_box(bb);
+    }
+
+    /// Shades the pixels between `min` and `max` of the canvas into a separate image
+    fn region(
+        &self,
+        min: [usize; 2],
+        max: [usize; 2],
+        shade: impl Fn(Vec3) -> Option<Vec4> + Sync,
+    ) -> Framebuffer {
+        shade_region(self.camera, self.width, self.height, min, max, shade)
+    }
 
-        let handle = self
-            .canvas
-            .ctx()
-            .load_texture("terrain", img, Default::default());
-        let textureid = TextureId::from(&handle);
+    /// Draws the enabled terrain overlays
+    fn draw_terrain_debug(&mut self, terrain: &Terrain) {
+        let debug = self.terrain_debug;
+        if debug.wireframe || debug.normals {
+            let wire = Stroke::new(0.5, Color32::from_gray(40));
+            let normal = Stroke::new(1.0, Color32::from_rgb(60, 90, 255));
+            let length = terrain.bounding_box.size().max_element() * 0.02;
+            for (mesh, (n0, n1, n2)) in &terrain.triangles {
+                let vertices = [mesh.0, mesh.1, mesh.2];
+                if debug.wireframe {
+                    for i in 0..3 {
+                        self.canvas
+                            .line(vertices[i], vertices[(i + 1) % 3], wire, self.mvp);
+                    }
+                }
+                if debug.normals {
+                    for (v, n) in vertices.into_iter().zip([n0, n1, n2]) {
+                        self.canvas
+                            .line(v, v + n.normalize() * length, normal, self.mvp);
+                    }
+                }
+            }
+        }
+        if debug.bounding_box {
+            self.visit_bounding_box(&terrain.bounding_box);
+        }
+    }
+
+    /// Draws a false colour view of the cloud, bypassing the cached cloud frames
+    fn draw_cloud_debug(&mut self, cloud: &Cloud) {
+        let view = self.cloud_debug;
+        let bb = *cloud.bounding_box();
+        let bounds = match view {
+            CloudDebugView::WeatherMap => {
+                BoundingBox::from((bb.min.with_y(0.0), bb.max.with_y(0.0)))
+            }
+            _ => bb,
+        };
+        let Some((min, max)) = box_bounds(self.camera, &bounds, self.width, self.height) else {
+            return;
+        };
+        let shader = self.lighting.cloud_shader(cloud, self.camera.pos());
+        let rays = PixelRays::new(self.camera, self.width, self.height);
+        let layer = &self.layer;
+        let img = shade_pixels(min, max, |x, y| {
+            let (ray_origin, ray_dir) = rays.pixel(x, y);
+            let col = shader.debug(ray_origin, ray_dir, layer.depth(x, y), view);
+            // The weather map lies on the ground, so it is drawn over the terrain
+            let depth = match view {
+                CloudDebugView::WeatherMap => 0.0,
+                _ => shader.entry(ray_origin, ray_dir),
+            };
+            Some((col, depth))
+        });
+        self.layer.blend(&img, min);
+    }
+
+    /// Tone maps `img` and paints it onto the canvas with its top left corner at pixel `min`
+    fn blit(&self, name: &str, img: &Framebuffer, min: [usize; 2], options: egui::TextureOptions) {
+        let start = Instant::now();
+        let handle =
+            self.canvas
+                .ctx()
+                .load_texture(name, img.to_color_image(&self.tone_mapping), options);
+        self.counters.upload(start.elapsed());
+        let min = self.canvas.resp_rect().min + egui::vec2(min[0] as f32, min[1] as f32);
         self.canvas.image(
-            textureid,
-            egui::Rect::from_two_pos(min_tuple, max_tuple),
+            TextureId::from(&handle),
+            egui::Rect::from_min_size(min, egui::vec2(img.width() as f32, img.height() as f32)),
             egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
             Color32::WHITE,
         );
     }
-}
-
-fn interpolate<T>(pos: Pos2, v0: Pos2, v1: Pos2, v2: Pos2, n0: T, n1: T, n2: T) -> T
-where
-    T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
-{
-    let area_total = (v1.x - v0.x) * (v2.y - v0.y) - (v2.x - v0.x) * (v1.y - v0.y);
-    let alpha = ((v1.x - pos.x) * (v2.y - pos.y) - (v2.x - pos.x) * (v1.y - pos.y)) / area_total;
-    let beta = ((v2.x - pos.x) * (v0.y - pos.y) - (v0.x - pos.x) * (v2.y - pos.y)) / area_total;
-    let gamma = 1.0 - alpha - beta;
-
-    n0 * alpha + n1 * beta + n2 * gamma
-}
-
-#[inline]
-fn sign(p1: Pos2, p2: Pos2, p3: Pos2) -> f32 {
-    (p1.x - p3.x) * (p2.y - p3.y) - (p2.x - p3.x) * (p1.y - p3.y)
-}
-
-fn inside_triangle(p: Pos2, v1: Pos2, v2: Pos2, v3: Pos2) -> bool {
-    let d1 = sign(p, v1, v2);
-    let d2 = sign(p, v2, v3);
-    let d3 = sign(p, v3, v1);
 
-    let has_neg = (d1 < 0.) || (d2 < 0.) || (d3 < 0.);
-    let has_pos = (d1 > 0.) || (d2 > 0.) || (d3 > 0.);
+    /// Draws a full resolution overlay over the sky around `dir`.
+    /// `shade` returns the colour of a pixel given its view direction.
+    fn draw_disk(
+        &self,
+        name: &str,
+        dir: Vec3,
+        angular_radius: f32,
+        shade: impl Fn(Vec3) -> Option<Vec4> + Sync,
+    ) {
+        let Some((min, max)) =
+            disk_bounds(self.camera, dir, angular_radius, self.width, self.height)
+        else {
+            return;
+        };
+        let img = self.region(min, max, shade);
+        self.blit(name, &img, min, egui::TextureOptions::NEAREST);
+    }
 
-    !(has_neg && has_pos)
-}
+    /// Draws the star field at full resolution, fading it in as the sun sets
+    fn draw_stars(&self, sky: &Sky) {
+        let night = self.lighting.night();
+        if night <= 0.0 {
+            return;
+        }
 
-#[inline]
-fn color32_to_vec4(color32: Color32) -> Vec4 {
-    color32.to_array().map(|x| x as f32 / 255.0).into()
+        let height = self.camera.pos().y;
+        let img = self.region([0, 0], [self.width, self.height], |ray_dir| {
+            self.lighting.star_pixel(sky, height, night, ray_dir)
+        });
+        self.blit("stars", &img, [0, 0], egui::TextureOptions::NEAREST);
+    }
 }
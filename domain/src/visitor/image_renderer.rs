@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+
+use glam::{Vec3, Vec4};
+
+use crate::canvas::Framebuffer;
+use crate::object::camera::Camera;
+use crate::object::objects::{Cloud, Sky, Terrain};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::sampling::Supersampling;
+use crate::visitor::shading::{
+    box_bounds, disk_bounds, shade_pixels, shade_region, Lighting, PixelRays,
+};
+use crate::visitor::{Visitable, Visitor};
+
+/// Renders a scene into a [`Framebuffer`] without a window or an egui context.
+/// Debug geometry such as the grid and the light markers is not drawn.
+pub struct ImageRenderer<'a> {
+    camera: &'a Camera,
+    width: usize,
+    height: usize,
+    lighting: Lighting,
+    supersampling: Supersampling,
+    framebuffer: Framebuffer,
+}
+
+impl<'a> ImageRenderer<'a> {
+    pub fn new(camera: &'a Camera, width: usize, height: usize) -> Self {
+        Self {
+            camera,
+            width,
+            height,
+            lighting: Lighting::default(),
+            supersampling: Supersampling::default(),
+            framebuffer: Framebuffer::new(width, height),
+        }
+    }
+
+    pub fn with_supersampling(mut self, supersampling: Supersampling) -> Self {
+        self.supersampling = supersampling;
+        self
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn into_framebuffer(self) -> Framebuffer {
+        self.framebuffer
+    }
+
+    /// Shades every pixel of the frame
+    fn layer(&self, shade: impl Fn(Vec3) -> Option<Vec4> + Sync) -> Framebuffer {
+        self.region([0, 0], [self.width, self.height], shade)
+    }
+
+    /// Shades the pixels between `min` and `max` into a separate image
+    fn region(
+        &self,
+        min: [usize; 2],
+        max: [usize; 2],
+        shade: impl Fn(Vec3) -> Option<Vec4> + Sync,
+    ) -> Framebuffer {
+        shade_region(self.camera, self.width, self.height, min, max, shade)
+    }
+
+    fn draw_disk(
+        &mut self,
+        dir: Vec3,
+        angular_radius: f32,
+        shade: impl Fn(Vec3) -> Option<Vec4> + Sync,
+    ) {
+        let Some((min, max)) =
+            disk_bounds(self.camera, dir, angular_radius, self.width, self.height)
+        else {
+            return;
+        };
+        let img = self.region(min, max, shade);
+        self.framebuffer.blend(&img, min);
+    }
+}
+
+impl<'a> Visitor for ImageRenderer<'a> {
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+        self.lighting = Lighting::gather(scene_objects);
+        if let Some(sky) = self.lighting.sky {
+            self.visit_sky(&sky);
+        }
+
+        let mut objs = scene_objects
+            .values()
+            .filter(|x| !matches!(x, Component::Sky(_)))
+            .collect::<Vec<_>>();
+        objs.sort_by(|x, y| {
+            (y.pos() - self.camera.pos())
+                .length()
+                .partial_cmp(&(x.pos() - self.camera.pos()).length())
+                .unwrap_or(Ordering::Greater)
+        });
+
+        // Terrain goes first so that its depth clips the cloud rays
+        objs.sort_by_key(|x| match x {
+            Component::Terrain(_) => 0,
+            Component::Cloud(_) => 1,
+            _ => 2,
+        });
+        for i in objs {
+            i.accept(self);
+        }
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        let shader = self.lighting.cloud_shader(cloud, self.camera.pos());
+        let Some((min, max)) =
+            box_bounds(self.camera, cloud.bounding_box(), self.width, self.height)
+        else {
+            return;
+        };
+        let rays = PixelRays::new(self.camera, self.width, self.height);
+        let (depth, supersampling) = (&self.framebuffer, &self.supersampling);
+        let img = shade_pixels(min, max, |x, y| {
+            let col = shader.shade_pixel(&rays, x, y, supersampling, depth.depth(x, y));
+            let (ray_origin, ray_dir) = rays.pixel(x, y);
+            Some((col, shader.entry(ray_origin, ray_dir)))
+        });
+        self.framebuffer.blend(&img, min);
+    }
+
+    fn visit_sky(&mut self, sky: &Sky) {
+        if !self.lighting.is_lit() {
+            return;
+        }
+        let lighting = &self.lighting;
+        let height = self.camera.pos().y;
+
+        let img = self.layer(|ray_dir| Some(lighting.sky_pixel(sky, height, ray_dir)));
+        self.framebuffer.blend(&img, [0, 0]);
+
+        let night = lighting.night();
+        if night > 0.0 {
+            let img = self.layer(|ray_dir| lighting.star_pixel(sky, height, night, ray_dir));
+            self.framebuffer.blend(&img, [0, 0]);
+        }
+        let (sun, moon) = (self.lighting.sun, self.lighting.moon);
+        if let Some(moon) = moon {
+            let shade = Lighting::moon_disk(sky, &moon, height);
+            self.draw_disk(moon.dir(), moon.angular_radius(), shade);
+        }
+        if let Some(sun) = sun {
+            let shade = Lighting::sun_disk(sky, &sun, height);
+            self.draw_disk(sun.dir(), sun.angular_radius(), shade);
+        }
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) {
+        if !self.lighting.is_lit() {
+            return;
+        }
+        let img = self.lighting.rasterize_terrain(
+            terrain,
+            self.camera,
+            self.width,
+            self.height,
+            &self.supersampling.offsets(),
+            None,
+        );
+        self.framebuffer.blend(&img, [0, 0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::{Sky, Sun};
+    use crate::scene::scene::Scene;
+
+    #[test]
+    fn test_render_sky_without_egui() {
+        let mut scene = Scene::default();
+        scene.add_object("sun", Sun::new(10.0, -45.0, -90.0));
+        scene.add_object("sky", Sky::default());
+        // The top row of the default view lies on the horizon
+        let mut camera = Camera::default();
+        camera.view.pitch = 0.3;
+
+        let mut renderer = ImageRenderer::new(&camera, 32, 24);
+        scene.accept(&mut renderer);
+        let fb = renderer.into_framebuffer();
+
+        assert_eq!((fb.width(), fb.height()), (32, 24));
+        assert!(fb.pixels().iter().all(|p| (p.w - 1.0).abs() < 1e-5));
+        let top = fb.get(16, 0);
+        assert!(top.z > top.x, "{:?}", top);
+    }
+}
@@ -1,9 +1,16 @@
 use crate::object::camera::Camera;
 use crate::object::objects::cloud::Cloud;
-use crate::object::objects::{BoundingBox, Grid, Sun, Terrain};
+use crate::object::objects::{BoundingBox, Grid, Moon, Sky, Sun, Terrain};
 use crate::scene::scene_composite::SceneObjects;
 
 pub mod draw_visitor;
+pub mod image_renderer;
+pub mod progressive;
+mod raster;
+pub mod sampling;
+mod shading;
+pub mod stats;
+pub mod temporal;
 
 pub trait Visitable {
     fn accept(&self, visitor: &mut impl Visitor);
@@ -22,6 +29,8 @@ pub trait Visitor: Sized + Send + Sync {
     fn visit_grid(&mut self, _grid: &Grid) {}
     fn visit_bounding_box(&mut self, _bb: &BoundingBox) {}
     fn visit_sun(&mut self, _bb: &Sun) {}
+    fn visit_moon(&mut self, _moon: &Moon) {}
+    fn visit_sky(&mut self, _sky: &Sky) {}
 
     fn visit_terrain(&mut self, _terrain: &Terrain) {}
 }
@@ -0,0 +1,184 @@
+//! Cloud layer rendered tile by tile over several frames, coarse first
+
+use std::time::{Duration, Instant};
+
+use glam::{Mat4, Vec4};
+
+use crate::canvas::Framebuffer;
+use crate::object::camera::{PhysicalCamera, ProjectionKind};
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::{Moon, Sky, Sun};
+
+/// Side of a square tile in pixels
+pub const TILE_SIZE: usize = 32;
+
+/// Pixel steps of the refinement passes. A pass shades one pixel in every
+/// `step` x `step` block and stretches it over the block until a finer pass replaces it.
+const PASSES: [usize; 3] = [4, 2, 1];
+
+/// Everything a cloud frame depends on, the cached frame is kept while it stays the same
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudFrameKey {
+    pub view_proj: Mat4,
+    /// The matrix of a panorama is only used by the overlays and misses its rays
+    pub projection: ProjectionKind,
+    /// Depth of field and the shutter change the rays of every pixel
+    pub physical: PhysicalCamera,
+    /// Bounds of the cloud on the canvas in pixels
+    pub min: [usize; 2],
+    pub max: [usize; 2],
+    pub cloud: CloudBuilder,
+    pub sun: Option<Sun>,
+    pub moon: Option<Moon>,
+    pub sky: Option<Sky>,
+}
+
+/// Partially rendered cloud frame carried over between frames
+#[derive(Debug, Default)]
+pub struct ProgressiveCloud {
+    key: Option<CloudFrameKey>,
+    framebuffer: Framebuffer,
+    pass: usize,
+    /// Next tile of the current pass
+    tile: usize,
+}
+
+impl ProgressiveCloud {
+    /// Fraction of the work done, `1.0` once the frame is at full quality
+    pub fn progress(&self) -> f32 {
+        let tiles = self.tiles().max(1);
+        ((self.pass * tiles + self.tile) as f32 / (PASSES.len() * tiles) as f32).min(1.0)
+    }
+
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.pass >= PASSES.len()
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Number of tiles covering the cloud bounds
+    fn tiles(&self) -> usize {
+        let (w, h) = (self.framebuffer.width(), self.framebuffer.height());
+        w.div_ceil(TILE_SIZE) * h.div_ceil(TILE_SIZE)
+    }
+
+    /// Restarts from the coarsest pass when `key` differs from the cached one.
+    /// The previous image stays visible until the new passes cover it.
+    fn restart(&mut self, key: CloudFrameKey) {
+        let (w, h) = (key.max[0] - key.min[0], key.max[1] - key.min[1]);
+        if (self.framebuffer.width(), self.framebuffer.height()) != (w, h) {
+            self.framebuffer = Framebuffer::new(w, h);
+        }
+        self.key = Some(key);
+        self.pass = 0;
+        self.tile = 0;
+    }
+
+    /// Shades tiles until `budget` runs out or the frame is complete.
+    /// `shade` returns the colour of a canvas pixel given its column and row.
+    pub fn render(
+        &mut self,
+        key: CloudFrameKey,
+        budget: Duration,
+        shade: impl Fn(usize, usize) -> Vec4 + Sync,
+    ) -> &Framebuffer {
+        use rayon::prelude::*;
+
+        if self.key.as_ref() != Some(&key) {
+            self.restart(key);
+        }
+        let Some(key) = &self.key else {
+            return &self.framebuffer;
+        };
+        let (min, width, height) = (key.min, self.framebuffer.width(), self.framebuffer.height());
+        let columns = width.div_ceil(TILE_SIZE);
+        let tiles = self.tiles();
+
+        let start = Instant::now();
+        // At least one batch per frame, so that a tight budget still makes progress
+        while !self.is_complete() {
+            let step = PASSES[self.pass];
+            let prev = self.pass.checked_sub(1).map(|pass| PASSES[pass]);
+            let batch = (self.tile..tiles)
+                .take(rayon::current_num_threads())
+                .collect::<Vec<_>>();
+
+            let samples = batch
+                .par_iter()
+                .flat_map_iter(|&tile| {
+                    let (x0, y0) = ((tile % columns) * TILE_SIZE, (tile / columns) * TILE_SIZE);
+                    let xs = (x0..(x0 + TILE_SIZE).min(width)).step_by(step);
+                    let ys = (y0..(y0 + TILE_SIZE).min(height)).step_by(step);
+                    ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+                        // Pixels of the coarser pass are already shaded
+                        .filter(move |&(x, y)| prev.is_none_or(|p| x % p != 0 || y % p != 0))
+                        .map(|(x, y)| (x, y, shade(x + min[0], y + min[1])))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            for (x, y, col) in samples {
+                for by in y..(y + step).min(height) {
+                    for bx in x..(x + step).min(width) {
+                        self.framebuffer.set(bx, by, col);
+                    }
+                }
+            }
+
+            self.tile += batch.len();
+            if self.tile >= tiles {
+                self.pass += 1;
+                self.tile = 0;
+            }
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+        &self.framebuffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(min: [usize; 2], max: [usize; 2]) -> CloudFrameKey {
+        CloudFrameKey {
+            view_proj: Mat4::IDENTITY,
+            projection: ProjectionKind::Perspective,
+            physical: PhysicalCamera::default(),
+            min,
+            max,
+            cloud: CloudBuilder::default(),
+            sun: None,
+            moon: None,
+            sky: None,
+        }
+    }
+
+    #[test]
+    fn test_converges_to_full_quality() {
+        let shade = |x: usize, y: usize| Vec4::new(x as f32, y as f32, 0.0, 1.0);
+        let mut cloud = ProgressiveCloud::default();
+
+        cloud.render(key([10, 20], [110, 70]), Duration::ZERO, shade);
+        assert!(!cloud.is_complete());
+        assert!(cloud.progress() > 0.0);
+
+        while !cloud.is_complete() {
+            cloud.render(key([10, 20], [110, 70]), Duration::ZERO, shade);
+        }
+        let fb = cloud.framebuffer();
+        assert_eq!((fb.width(), fb.height()), (100, 50));
+        for (x, y) in [(0, 0), (33, 17), (99, 49)] {
+            assert_eq!(fb.get(x, y), shade(x + 10, y + 20));
+        }
+
+        // A new camera or parameters start over from the coarse pass
+        cloud.render(key([0, 0], [100, 50]), Duration::ZERO, shade);
+        assert!(!cloud.is_complete());
+    }
+}
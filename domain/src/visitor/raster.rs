@@ -0,0 +1,270 @@
+//! Binned triangle rasteriser. Triangles are sorted into screen tiles first, then every
+//! tile is rasterised by a single worker into its own depth and visibility buffers,
+//! so no pixel is ever shared between threads.
+
+use egui::Pos2;
+use glam::{Vec2, Vec3, Vec4};
+
+use crate::canvas::Framebuffer;
+use crate::object::camera::Camera;
+use crate::visitor::shading::PixelRays;
+
+/// Side of a square screen tile in pixels
+const TILE: usize = 16;
+
+/// Triangle with its projection on the frame and per-vertex attributes
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct RasterTriangle<A> {
+    pub world: [Vec3; 3],
+    /// Vertices in pixels
+    pub screen: [Pos2; 3],
+    pub attrs: [A; 3],
+}
+
+impl<A> RasterTriangle<A> {
+    /// Distance along the ray to the plane of the triangle, `None` when the plane is
+    /// parallel to the ray or behind its origin
+    fn intersect(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<f32> {
+        let [p0, p1, p2] = self.world;
+        let normal = (p1 - p0).cross(p2 - p0);
+        let facing = normal.dot(ray_dir);
+        let depth = normal.dot(p0 - ray_origin) / facing;
+        (facing != 0.0 && depth > 0.0).then_some(depth)
+    }
+
+    /// Barycentric coordinates of a point on the plane of the triangle. They are computed
+    /// in world space, so attributes interpolated with them are perspective-correct.
+    pub fn barycentric(&self, point: Vec3) -> [f32; 3] {
+        let [p0, p1, p2] = self.world;
+        let normal = (p1 - p0).cross(p2 - p0);
+        let area = normal.length_squared();
+        let b0 = (p2 - p1).cross(point - p1).dot(normal) / area;
+        let b1 = (p0 - p2).cross(point - p2).dot(normal) / area;
+        [b0, b1, 1.0 - b0 - b1]
+    }
+
+    /// Bounds of the pixels whose samples may fall into the triangle, clipped to
+    /// a `width` x `height` frame. Samples are at most half a pixel away from the pixel.
+    fn bounds(&self, width: usize, height: usize) -> Option<([usize; 2], [usize; 2])> {
+        let [v0, v1, v2] = self.screen;
+        let min = (v0.min(v1).min(v2) - egui::Vec2::splat(0.5)).ceil();
+        let max = (v0.max(v1).max(v2) + egui::Vec2::splat(0.5)).floor();
+        if max.x < 0.0 || max.y < 0.0 || min.x >= width as f32 || min.y >= height as f32 {
+            return None;
+        }
+        let min = [min.x.max(0.0) as usize, min.y.max(0.0) as usize];
+        let max = [
+            (max.x as usize + 1).min(width),
+            (max.y as usize + 1).min(height),
+        ];
+        (min[0] < max[0] && min[1] < max[1]).then_some((min, max))
+    }
+}
+
+/// Nearest triangle seen through a sample of a tile
+#[derive(Debug, Copy, Clone)]
+struct Fragment {
+    triangle: usize,
+    depth: f32,
+}
+
+/// Rasterises `triangles` into a `width` x `height` image taking a sample at every offset
+/// from the pixel position. `shade` is called once per visible sample with the nearest
+/// triangle, the barycentric coordinates of the hit, the ray and the depth. A pixel averages
+/// its samples, those that miss every triangle are transparent, and its depth is the
+/// distance from the camera to the nearest sample.
+pub(crate) fn rasterize<A: Sync>(
+    triangles: &[RasterTriangle<A>],
+    camera: &Camera,
+    width: usize,
+    height: usize,
+    offsets: &[Vec2],
+    shade: impl Fn(&RasterTriangle<A>, [f32; 3], Vec3, f32) -> Vec4 + Sync,
+) -> Framebuffer {
+    use rayon::prelude::*;
+
+    let columns = width.div_ceil(TILE);
+    let rows = height.div_ceil(TILE);
+    let mut bins = vec![Vec::new(); columns * rows];
+    for (index, triangle) in triangles.iter().enumerate() {
+        let Some((min, max)) = triangle.bounds(width, height) else {
+            continue;
+        };
+        for row in min[1] / TILE..=(max[1] - 1) / TILE {
+            for column in min[0] / TILE..=(max[0] - 1) / TILE {
+                bins[row * columns + column].push(index);
+            }
+        }
+    }
+
+    let rays = PixelRays::new(camera, width, height);
+    let samples = offsets.len().max(1);
+    let tiles = bins
+        .par_iter()
+        .enumerate()
+        .map(|(tile, bin)| {
+            let (x0, y0) = ((tile % columns) * TILE, (tile / columns) * TILE);
+            let (x1, y1) = ((x0 + TILE).min(width), (y0 + TILE).min(height));
+            let mut fragments: Vec<Option<Fragment>> = vec![None; TILE * TILE * samples];
+
+            for &index in bin {
+                let triangle = &triangles[index];
+                let [v0, v1, v2] = triangle.screen;
+                let Some((min, max)) = triangle.bounds(width, height) else {
+                    continue;
+                };
+                for y in min[1].max(y0)..max[1].min(y1) {
+                    for x in min[0].max(x0)..max[0].min(x1) {
+                        let pixel = ((y - y0) * TILE + x - x0) * samples;
+                        for (sample, offset) in offsets.iter().enumerate() {
+                            let (sx, sy) = (x as f32 + offset.x, y as f32 + offset.y);
+                            if !inside_triangle(Pos2::new(sx, sy), v0, v1, v2) {
+                                continue;
+                            }
+                            let (ray_origin, ray_dir) = rays.at(sx, sy);
+                            let Some(depth) = triangle.intersect(ray_origin, ray_dir) else {
+                                continue;
+                            };
+                            let fragment = &mut fragments[pixel + sample];
+                            if fragment.is_none_or(|f| depth < f.depth) {
+                                *fragment = Some(Fragment {
+                                    triangle: index,
+                                    depth,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+
+            let mut pixels = [(Vec4::ZERO, f32::INFINITY); TILE * TILE];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let i = (y - y0) * TILE + x - x0;
+                    for (sample, offset) in offsets.iter().enumerate() {
+                        let Some(Fragment { triangle, depth }) = fragments[i * samples + sample]
+                        else {
+                            continue;
+                        };
+                        let triangle = &triangles[triangle];
+                        let (ray_origin, ray_dir) =
+                            rays.at(x as f32 + offset.x, y as f32 + offset.y);
+                        let bary = triangle.barycentric(ray_origin + ray_dir * depth);
+                        let (col, nearest) = &mut pixels[i];
+                        *col += shade(triangle, bary, ray_dir, depth) / samples as f32;
+                        *nearest = nearest.min(depth);
+                    }
+                }
+            }
+            pixels
+        })
+        .collect::<Vec<_>>();
+
+    let mut img = Framebuffer::new(width, height);
+    for (tile, pixels) in tiles.iter().enumerate() {
+        let (x0, y0) = ((tile % columns) * TILE, (tile / columns) * TILE);
+        for y in y0..(y0 + TILE).min(height) {
+            for x in x0..(x0 + TILE).min(width) {
+                let (col, depth) = pixels[(y - y0) * TILE + x - x0];
+                img.set(x, y, col);
+                img.set_depth(x, y, depth);
+            }
+        }
+    }
+    img
+}
+
+#[inline]
+fn sign(p1: Pos2, p2: Pos2, p3: Pos2) -> f32 {
+    (p1.x - p3.x) * (p2.y - p3.y) - (p2.x - p3.x) * (p1.y - p3.y)
+}
+
+fn inside_triangle(p: Pos2, v1: Pos2, v2: Pos2, v3: Pos2) -> bool {
+    let d1 = sign(p, v1, v2);
+    let d2 = sign(p, v2, v3);
+    let d3 = sign(p, v3, v1);
+
+    let has_neg = (d1 < 0.) || (d2 < 0.) || (d3 < 0.);
+    let has_pos = (d1 > 0.) || (d2 > 0.) || (d3 > 0.);
+
+    !(has_neg && has_pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Square facing the camera at `distance` along its view direction, `size` across
+    fn square(
+        camera: &Camera,
+        frame: (f32, f32),
+        distance: f32,
+        size: f32,
+        id: f32,
+    ) -> [RasterTriangle<f32>; 2] {
+        let forward = camera.dir();
+        let right = forward.cross(Vec3::Y).normalize() * size * 0.5;
+        let up = right.cross(forward).normalize() * size * 0.5;
+        let center = camera.pos() + forward * distance;
+        let corners = [
+            center - right - up,
+            center + right - up,
+            center + right + up,
+            center - right + up,
+        ];
+        let triangle = |world: [Vec3; 3]| RasterTriangle {
+            world,
+            screen: world.map(|p| camera.project(p, frame.0, frame.1).unwrap()),
+            attrs: [id; 3],
+        };
+        [
+            triangle([corners[0], corners[1], corners[2]]),
+            triangle([corners[0], corners[2], corners[3]]),
+        ]
+    }
+
+    #[test]
+    fn test_nearest_triangle_wins() {
+        let camera = Camera::default();
+        let (w, h) = (40, 30);
+        let frame = (w as f32, h as f32);
+        let distance = 10.0;
+        // The far square is drawn last and covers the whole frame
+        let mut triangles = square(&camera, frame, distance * 0.5, 1.0, 1.0).to_vec();
+        triangles.extend(square(&camera, frame, distance, distance * 4.0, 2.0));
+
+        let img = rasterize(
+            &triangles,
+            &camera,
+            w,
+            h,
+            &[Vec2::ZERO],
+            |triangle, bary, _, _| {
+                let id = triangle.attrs[0] * bary[0]
+                    + triangle.attrs[1] * bary[1]
+                    + triangle.attrs[2] * bary[2];
+                Vec4::new(id, 0.0, 0.0, 1.0)
+            },
+        );
+
+        let center = img.get(w / 2, h / 2);
+        assert!((center.x - 1.0).abs() < 1e-4, "{:?}", center);
+        assert!((img.depth(w / 2, h / 2) - distance * 0.5).abs() < 1e-2);
+        assert!((img.get(1, 1).x - 2.0).abs() < 1e-4);
+        // Off-centre rays hit the far plane further away
+        assert!(img.depth(1, 1) > distance);
+
+        // The diagonal edge of a supersampled triangle partially covers pixels
+        let offsets = [-0.25, 0.25].map(|x| [-0.25, 0.25].map(|y| Vec2::new(x, y)));
+        let img = rasterize(
+            &triangles[..1],
+            &camera,
+            w,
+            h,
+            offsets.as_flattened(),
+            |_, _, _, _| Vec4::ONE,
+        );
+        assert!(img.pixels().iter().any(|p| p.w > 0.0 && p.w < 1.0));
+        assert!(img.pixels().iter().all(|p| p.w <= 1.0 + 1e-5));
+    }
+}
@@ -0,0 +1,182 @@
+//! Sample positions inside a pixel for supersampling anti-aliasing
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// Arrangement of the samples inside a pixel
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SamplePattern {
+    /// Rows and columns aligned with the pixel grid
+    #[default]
+    Regular,
+    /// The regular grid rotated by `atan(1/2)`, so that no two samples share a row or
+    /// a column and near-horizontal edges get as many levels as the sample count
+    RotatedGrid,
+}
+
+impl SamplePattern {
+    pub const ALL: [SamplePattern; 2] = [Self::Regular, Self::RotatedGrid];
+}
+
+/// Supersampling settings, `grid` x `grid` samples per pixel
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Supersampling {
+    pub pattern: SamplePattern,
+    pub grid: usize,
+    /// Cloud samples are jittered inside their cells, which trades banding for noise
+    pub cloud_jitter: bool,
+}
+
+impl Default for Supersampling {
+    fn default() -> Self {
+        Self {
+            pattern: SamplePattern::Regular,
+            grid: 1,
+            cloud_jitter: false,
+        }
+    }
+}
+
+impl Supersampling {
+    pub fn new(pattern: SamplePattern, grid: usize) -> Self {
+        Self {
+            pattern,
+            grid: grid.max(1),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_cloud_jitter(mut self, cloud_jitter: bool) -> Self {
+        self.cloud_jitter = cloud_jitter;
+        self
+    }
+
+    #[inline]
+    pub fn samples(&self) -> usize {
+        self.grid.max(1).pow(2)
+    }
+
+    /// Offsets of the samples from the pixel position, at most half a pixel away
+    pub fn offsets(&self) -> Vec<Vec2> {
+        self.cells(|_| Vec2::splat(0.5))
+    }
+
+    /// Offsets of the cloud samples of the pixel in column `x` and row `y`,
+    /// every sample jittered inside its cell when `cloud_jitter` is set
+    pub fn cloud_offsets(&self, x: usize, y: usize) -> Vec<Vec2> {
+        if !self.cloud_jitter {
+            return self.offsets();
+        }
+        self.cells(|cell| {
+            let seed =
+                hash((y as u32).wrapping_mul(0x9e37_79b9) ^ x as u32 ^ ((cell as u32) << 24));
+            Vec2::new(unit(seed), unit(hash(seed)))
+        })
+    }
+
+    /// Offsets of the cells of the grid with the sample at `position(cell)` inside each cell
+    fn cells(&self, position: impl Fn(usize) -> Vec2) -> Vec<Vec2> {
+        let n = self.grid.max(1);
+        if n == 1 && !self.cloud_jitter {
+            return vec![Vec2::ZERO];
+        }
+        let (sin, cos) = 0.5f32.atan().sin_cos();
+        (0..n * n)
+            .map(|cell| {
+                let p = position(cell);
+                let grid = Vec2::new((cell % n) as f32 + p.x, (cell / n) as f32 + p.y) / n as f32;
+                let offset = grid - Vec2::splat(0.5);
+                match self.pattern {
+                    SamplePattern::Regular => offset,
+                    SamplePattern::RotatedGrid => {
+                        // Scaled so that the 2x2 grid lands on the classic RGSS positions,
+                        // larger grids wrap around into the pixel
+                        let rotated = Vec2::new(
+                            offset.x * cos - offset.y * sin,
+                            offset.x * sin + offset.y * cos,
+                        ) / cos;
+                        (rotated + Vec2::splat(0.5)).map(|x| x.rem_euclid(1.0)) - Vec2::splat(0.5)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Lens positions in `[0, 1)²` and shutter positions in `[0, 1)` of `count` rays through
+/// the lens for sample `cell` of the pixel in column `x` and row `y`. A Hammersley set
+/// shifted randomly per pixel and sample, so that the noise does not form a pattern.
+pub fn lens_samples(x: usize, y: usize, cell: usize, count: usize) -> Vec<(Vec2, f32)> {
+    let seed = hash((y as u32).wrapping_mul(0x9e37_79b9) ^ x as u32 ^ ((cell as u32) << 24));
+    let shift = [unit(seed), unit(hash(seed)), unit(hash(seed ^ 0x5bd1_e995))];
+    (0..count)
+        .map(|i| {
+            let lens = Vec2::new(
+                (i as f32 + 0.5) / count as f32 + shift[0],
+                radical_inverse(i, 2) + shift[1],
+            );
+            (lens.fract(), (radical_inverse(i, 3) + shift[2]).fract())
+        })
+        .collect()
+}
+
+/// Digits of `i` in the given base mirrored around the point
+fn radical_inverse(mut i: usize, base: usize) -> f32 {
+    let (mut result, mut scale) = (0.0, 1.0 / base as f32);
+    while i > 0 {
+        result += (i % base) as f32 * scale;
+        i /= base;
+        scale /= base as f32;
+    }
+    result
+}
+
+#[inline]
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^ (x >> 16)
+}
+
+/// Maps a hash to `[0, 1)`
+#[inline]
+fn unit(x: u32) -> f32 {
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets() {
+        assert_eq!(Supersampling::default().offsets(), vec![Vec2::ZERO]);
+
+        let regular = Supersampling::new(SamplePattern::Regular, 2).offsets();
+        assert_eq!(regular[0], Vec2::splat(-0.25));
+        assert_eq!(regular[3], Vec2::splat(0.25));
+
+        let rotated = Supersampling::new(SamplePattern::RotatedGrid, 2).offsets();
+        let expected = Vec2::new(-0.125, -0.375);
+        assert!((rotated[0] - expected).length() < 1e-5, "{:?}", rotated);
+
+        let lens = lens_samples(7, 3, 1, 8);
+        assert_eq!(lens.len(), 8);
+        assert!(lens
+            .iter()
+            .all(|(l, t)| l.cmpge(Vec2::ZERO).all() && l.cmplt(Vec2::ONE).all() && *t < 1.0));
+        assert_eq!(radical_inverse(6, 2), 0.375);
+
+        for ss in [
+            Supersampling::new(SamplePattern::RotatedGrid, 4),
+            Supersampling::new(SamplePattern::Regular, 3).with_cloud_jitter(true),
+        ] {
+            let offsets = ss.cloud_offsets(7, 3);
+            assert_eq!(offsets.len(), ss.samples());
+            assert!(offsets.iter().all(|o| o.abs().max_element() <= 0.5));
+        }
+    }
+}
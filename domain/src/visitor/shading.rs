@@ -0,0 +1,670 @@
+//! Per-pixel shading shared by the on-screen and the offscreen renderers
+
+use egui::Pos2;
+use glam::{Vec3, Vec3Swizzles, Vec4};
+
+use crate::canvas::{color, Framebuffer};
+use crate::object::camera::{Camera, ProjectionKind};
+use crate::object::objects::cloud::{beer, hg, phase};
+use crate::object::objects::sky::{star_field, AerialPerspective};
+use crate::object::objects::{BoundingBox, Cloud, DirectionalLight, Moon, Sky, Sun, Terrain};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::draw_visitor::CloudDebugView;
+use crate::visitor::raster::{rasterize, RasterTriangle};
+use crate::visitor::sampling::{lens_samples, Supersampling};
+use crate::visitor::stats::PassCounters;
+
+/// Radiance of the brightest star
+const STAR_RADIANCE: f32 = 2.0;
+
+/// Light that dominates the scene: the sun by day and the moon at night
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct KeyLight {
+    pub light: DirectionalLight,
+    /// Position used by the point-light style shading of clouds and terrain
+    pub pos: Vec3,
+    /// Direct radiance reaching the lit object
+    pub radiance: Vec3,
+}
+
+/// Lights and participating media of a scene, gathered before drawing
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Lighting {
+    pub sun: Option<Sun>,
+    pub moon: Option<Moon>,
+    pub sky: Option<Sky>,
+    /// Casts shadows on the terrain
+    pub cloud: Option<Cloud>,
+}
+
+impl Lighting {
+    pub fn gather(scene_objects: &SceneObjects) -> Self {
+        let mut lighting = Self::default();
+        for obj in scene_objects.values() {
+            match obj {
+                Component::Sun(sun) => lighting.sun = Some(*sun),
+                Component::Moon(moon) => lighting.moon = Some(*moon),
+                Component::Sky(sky) => lighting.sky = Some(**sky),
+                Component::Cloud(cloud) => lighting.cloud = Some((**cloud).clone()),
+                _ => {}
+            }
+        }
+        lighting
+    }
+
+    #[inline]
+    pub fn is_lit(&self) -> bool {
+        self.sun.is_some() || self.moon.is_some()
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item = DirectionalLight> {
+        let sun = self.sun.map(|sun| sun.light());
+        let moon = self.moon.map(|moon| moon.light());
+        sun.into_iter().chain(moon)
+    }
+
+    /// Visibility of the stars, fades in as the sun sets
+    pub fn night(&self) -> f32 {
+        self.sun
+            .map_or(1.0, |sun| (-sun.dir().y / 0.1).clamp(0.0, 1.0))
+    }
+
+    /// Picks the brightest light at the given height, the moon takes over once the sun sets
+    pub fn key_light(&self, height: f32) -> KeyLight {
+        let sun = self.sun.unwrap_or_default();
+        let sun_key = KeyLight {
+            light: sun.light(),
+            pos: sun.get_pos(),
+            radiance: self.sky.map_or(sun.light().radiance, |sky| {
+                sky.sun_light(height, sun.light())
+            }),
+        };
+        let Some(moon) = self.moon else {
+            return sun_key;
+        };
+
+        let light = moon.light();
+        let moon_key = KeyLight {
+            light,
+            pos: light.dir * sun.get_pos().length(),
+            radiance: self
+                .sky
+                .map_or(light.radiance, |sky| sky.sun_light(height, light)),
+        };
+        let luminance = |key: &KeyLight| {
+            let above_horizon = self.sky.is_some() || key.light.dir.y > 0.0;
+            f32::from(above_horizon) * key.radiance.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+        };
+        if self.sun.is_none() || luminance(&moon_key) > luminance(&sun_key) {
+            moon_key
+        } else {
+            sun_key
+        }
+    }
+
+    /// Sky radiance seen along `ray_dir` from the given height
+    pub fn sky_pixel(&self, sky: &Sky, height: f32, ray_dir: Vec3) -> Vec4 {
+        let radiance = self
+            .lights()
+            .map(|light| sky.radiance(height, ray_dir, light))
+            .sum::<Vec3>();
+        (radiance + sky.night_glow(height, ray_dir)).extend(1.0)
+    }
+
+    /// Star seen along `ray_dir`, `None` for the empty sky.
+    /// Stars have no coverage, so they add to whatever is behind them.
+    pub fn star_pixel(&self, sky: &Sky, height: f32, night: f32, ray_dir: Vec3) -> Option<Vec4> {
+        let star = star_field(ray_dir);
+        (star > 0.0).then(|| {
+            let brightness = star * night * sky.transmittance(height, ray_dir).y;
+            Vec3::splat(brightness * STAR_RADIANCE).extend(0.0)
+        })
+    }
+
+    /// Shades the limb-darkened sun disk, `None` outside of it
+    pub fn sun_disk(sky: &Sky, sun: &Sun, height: f32) -> impl Fn(Vec3) -> Option<Vec4> + Sync {
+        let light = sun.light();
+        let radius = sun.angular_radius();
+        let sun_light = sky.sun_light(height, light) * sky.intensity;
+
+        move |ray_dir| {
+            let r = ray_dir.dot(light.dir).clamp(-1.0, 1.0).acos() / radius;
+            (r < 1.0).then(|| (sun_light * Sun::limb_darkening(r)).extend(1.0))
+        }
+    }
+
+    /// Shades the lit part of the moon disk, the dark part lets the sky through
+    pub fn moon_disk(sky: &Sky, moon: &Moon, height: f32) -> impl Fn(Vec3) -> Option<Vec4> + Sync {
+        let moon = *moon;
+        let light = DirectionalLight {
+            dir: moon.dir(),
+            radiance: color::to_linear_rgb(moon.color) * moon.intensity,
+        };
+        let moon_light = sky.sun_light(height, light) * sky.intensity;
+
+        move |ray_dir| {
+            let lit = moon.disk(ray_dir);
+            (lit > 0.0).then(|| (moon_light * lit).extend(lit))
+        }
+    }
+
+    /// Prepares the lighting of a cloud seen by a viewer at `eye`
+    pub fn cloud_shader<'c>(&self, cloud: &'c Cloud, eye: Vec3) -> CloudShader<'c> {
+        let height = cloud.bounding_box().center().y;
+        let key = self.key_light(height);
+        let ambient = self.sky.map_or(Vec3::ZERO, |sky| {
+            let ambient = self
+                .lights()
+                .map(|light| sky.ambient(height, light))
+                .sum::<Vec3>();
+            ambient + sky.night_glow(height, Vec3::Y)
+        });
+        let aerial = self.sky.map(|sky| sky.aerial_perspective(eye.y, key.light));
+        CloudShader {
+            cloud,
+            key,
+            ambient,
+            aerial,
+            counters: None,
+        }
+    }
+
+    /// Rasterises the terrain into a `width` x `height` image as seen by the camera,
+    /// with a sample at every offset from the pixel position.
+    /// The depth of the image is the distance from the camera along the pixel rays.
+    /// The time spent on cloud shadows goes into `counters` when given.
+    pub fn rasterize_terrain(
+        &self,
+        terrain: &Terrain,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+        offsets: &[glam::Vec2],
+        counters: Option<&PassCounters>,
+    ) -> Framebuffer {
+        use rayon::prelude::*;
+
+        let key = self.key_light(terrain.bounding_box.center().y);
+        let sun_pos = key.pos;
+        let sun_color = key.radiance;
+        let aerial = self
+            .sky
+            .map(|sky| sky.aerial_perspective(camera.pos().y, key.light));
+        let cloud = self.cloud.as_ref();
+        let bb = terrain.bounding_box;
+
+        let bottom = color::to_linear_rgb(terrain.bottom_color);
+        let top = color::to_linear_rgb(terrain.top_color);
+
+        let march_shadow = |probe: Vec3| -> f32 {
+            let Some(cloud) = cloud else {
+                return 1.0;
+            };
+            let sun_dir = (sun_pos - probe).normalize();
+            let cloud_bb = cloud.bounding_box().dst(probe, sun_dir);
+            let (dir_to_box, dst_inside_box) = cloud_bb.into();
+            if dst_inside_box != 0.0 {
+                let mut p = probe;
+                let num_steps = terrain.num_shadows_steps;
+                let step_size = dst_inside_box / num_steps as f32;
+                p += dir_to_box * sun_dir;
+
+                let mut total_density = 0.0;
+
+                for _ in 0..num_steps {
+                    let density = cloud.sample_density(p);
+                    total_density += density.max(0.0) * step_size;
+                    p += sun_dir * step_size;
+                }
+                beer(total_density / terrain.density_scale).clamp(terrain.shadow_threshold, 1.0)
+            } else {
+                1.0
+            }
+        };
+        let get_shadow_factor = |probe: Vec3| match counters {
+            Some(counters) => {
+                let start = std::time::Instant::now();
+                let shadow = march_shadow(probe);
+                counters.shadow(start.elapsed());
+                shadow
+            }
+            None => march_shadow(probe),
+        };
+
+        // Lighting is evaluated at the vertices, the pixels interpolate it
+        let rays = PixelRays::new(camera, width, height);
+        let mut triangles = terrain
+            .triangles
+            .par_iter()
+            .filter_map(|(v, normals)| {
+                let (p0, p1, p2) = v.to_tuple();
+                let world = [p0, p1, p2];
+                let screen = [rays.project(p0)?, rays.project(p1)?, rays.project(p2)?];
+                let normals = [normals.0, normals.1, normals.2];
+                let attrs = [0, 1, 2].map(|i| {
+                    let p = world[i];
+                    // Faces turned away from the sun get no direct light, not negative light
+                    let alpha = (sun_pos - p)
+                        .normalize()
+                        .dot(normals[i].normalize())
+                        .max(0.0);
+                    let col = bottom.lerp(top, (p.y - bb.min.y).abs() / bb.size().y);
+                    (col, get_shadow_factor(p) * alpha)
+                });
+                Some(RasterTriangle {
+                    world,
+                    screen,
+                    attrs,
+                })
+            })
+            .collect::<Vec<_>>();
+        if camera.kind == ProjectionKind::Equirectangular {
+            wrap_seam(&mut triangles, width as f32);
+        }
+
+        rasterize(
+            &triangles,
+            camera,
+            width,
+            height,
+            offsets,
+            |triangle, bary, ray_dir, depth| {
+                let [(c0, l0), (c1, l1), (c2, l2)] = triangle.attrs;
+                let beta = l0 * bary[0] + l1 * bary[1] + l2 * bary[2];
+                let dif = terrain.diffuse_factor * beta;
+                let col = c0 * bary[0] + c1 * bary[1] + c2 * bary[2];
+                let col = col * dif * sun_color;
+                let col = match aerial {
+                    Some(aerial) => {
+                        let (extinction, in_scattering) = aerial.segment(ray_dir, depth);
+                        col * extinction + in_scattering
+                    }
+                    None => col,
+                };
+                col.extend(1.0)
+            },
+        )
+    }
+}
+
+/// Ray marches a single cloud with precomputed lighting
+pub(crate) struct CloudShader<'c> {
+    cloud: &'c Cloud,
+    key: KeyLight,
+    ambient: Vec3,
+    aerial: Option<AerialPerspective>,
+    counters: Option<&'c PassCounters>,
+}
+
+impl<'c> CloudShader<'c> {
+    /// Counts the rays and their steps into `counters`
+    pub fn with_counters(mut self, counters: &'c PassCounters) -> Self {
+        self.counters = Some(counters);
+        self
+    }
+
+    /// Distance along the ray to where it enters the cloud box, infinite when it misses
+    pub fn entry(&self, ray_origin: Vec3, ray_dir: Vec3) -> f32 {
+        let (dst_to_box, dst_inside_box) =
+            self.cloud.bounding_box().dst(ray_origin, ray_dir).into();
+        match dst_inside_box > 0.0 {
+            true => dst_to_box,
+            false => f32::INFINITY,
+        }
+    }
+
+    /// Average of [`CloudShader::shade`] over the samples of the pixel in column `x` and row `y`
+    pub fn shade_pixel(
+        &self,
+        rays: &PixelRays,
+        x: usize,
+        y: usize,
+        supersampling: &Supersampling,
+        max_dst: f32,
+    ) -> Vec4 {
+        let samples = rays.samples(x, y, &supersampling.cloud_offsets(x, y));
+        let total = samples
+            .iter()
+            .map(|&((ray_origin, ray_dir), time)| self.shade_at(ray_origin, ray_dir, max_dst, time))
+            .sum::<Vec4>();
+        total / samples.len() as f32
+    }
+
+    /// Premultiplied radiance of the cloud along the ray up to `max_dst`, the distance to
+    /// the nearest opaque surface, `time` seconds into the shutter interval with the clouds
+    /// carried by the wind. Transparent when the ray misses the cloud.
+    pub fn shade_at(&self, ray_origin: Vec3, ray_dir: Vec3, max_dst: f32, time: f32) -> Vec4 {
+        let cloud = self.cloud;
+        let offset = cloud.offset + cloud.wind * time;
+        let sun_pos = self.key.pos;
+        let sun_dir = self.key.light.dir;
+        let light_color = self.key.radiance;
+
+        let ray_box_info = cloud.bounding_box().dst(ray_origin, ray_dir);
+        let dst_to_box = ray_box_info.x;
+        let dst_inside_box = ray_box_info.y;
+
+        // Surfaces in front of the cloud or inside it cut the ray short
+        let dst_limit = dst_inside_box.min(max_dst - dst_to_box);
+        if dst_limit <= 0.0 {
+            return Vec4::ZERO;
+        }
+
+        let mut dst_travelled = 0.0;
+        let step_size = dst_inside_box / cloud.num_steps as f32;
+        let mut transmittance = 1.0;
+        let mut light_energy = 0.0;
+        let mut hit_distance = None;
+        let (mut steps, mut terminated) = (0, false);
+
+        let entry_point = ray_origin + dst_to_box * ray_dir;
+        let cos_angle = ray_dir.dot(sun_dir);
+        let phase = phase(cos_angle, cloud.phase_params);
+
+        while dst_travelled < dst_limit {
+            let ray_pos = entry_point + ray_dir * dst_travelled;
+            let density = cloud.sample_density_at(ray_pos, offset);
+            steps += 1;
+            if density > 0.1 {
+                hit_distance.get_or_insert(dst_to_box + dst_travelled);
+                let light_transmittance = cloud.light_march_at(ray_pos, sun_pos, offset);
+                light_energy += density * step_size * transmittance * light_transmittance * phase;
+                transmittance *= beer(density * step_size * cloud.light_absorption_through_cloud);
+                if transmittance < 0.01 {
+                    terminated = true;
+                    break;
+                }
+            }
+            dst_travelled += step_size;
+        }
+        if let Some(counters) = self.counters {
+            counters.ray(steps, terminated);
+        }
+
+        let focused_eye_cos = cos_angle.clamp(-1.0, 1.0).powf(cloud.params.x);
+        let sun = hg(focused_eye_cos, cloud.phase_params.w).clamp(-1.0, 1.0) * transmittance;
+
+        let cloud_col = light_energy * light_color + self.ambient;
+        let col = cloud_col * (1.0 - sun) + light_color * sun;
+        let col = match (self.aerial, hit_distance) {
+            (Some(aerial), Some(distance)) => {
+                let (extinction, in_scattering) = aerial.segment(ray_dir, distance);
+                col * extinction + in_scattering
+            }
+            _ => col,
+        };
+        let alpha = 1.0 - transmittance;
+        (col * alpha).extend(alpha)
+    }
+
+    /// False colour of the ray for a debug view, opaque wherever the ray crosses the cloud box.
+    /// The weather map is drawn where the ray hits the ground under the box.
+    pub fn debug(
+        &self,
+        ray_origin: Vec3,
+        ray_dir: Vec3,
+        max_dst: f32,
+        view: CloudDebugView,
+    ) -> Vec4 {
+        let cloud = self.cloud;
+        let bb = cloud.bounding_box();
+        if view == CloudDebugView::WeatherMap {
+            let dst = -ray_origin.y / ray_dir.y;
+            let ground = ray_origin + ray_dir * dst;
+            let under_box = (bb.min.xz().cmple(ground.xz()) & ground.xz().cmple(bb.max.xz())).all();
+            return match dst > 0.0 && under_box {
+                true => Vec3::splat(cloud.weather(ground)).extend(1.0),
+                false => Vec4::ZERO,
+            };
+        }
+
+        let (dst_to_box, dst_inside_box) = bb.dst(ray_origin, ray_dir).into();
+        let dst_limit = dst_inside_box.min(max_dst - dst_to_box);
+        if dst_limit <= 0.0 {
+            return Vec4::ZERO;
+        }
+        let cos_angle = ray_dir.dot(self.key.light.dir);
+        if view == CloudDebugView::Phase {
+            return heat(phase(cos_angle, cloud.phase_params)).extend(1.0);
+        }
+
+        let step_size = dst_inside_box / cloud.num_steps as f32;
+        let entry_point = ray_origin + dst_to_box * ray_dir;
+        let mut dst_travelled = 0.0;
+        let mut transmittance = 1.0;
+        let mut max_density = f32::NEG_INFINITY;
+        let (mut light, mut light_weight) = (0.0, 0.0);
+        let mut samples = 0;
+        while dst_travelled < dst_limit {
+            let ray_pos = entry_point + ray_dir * dst_travelled;
+            let density = cloud.sample_density(ray_pos);
+            samples += 1;
+            max_density = max_density.max(density);
+            if density > 0.1 {
+                let weight = density * transmittance;
+                light += cloud.light_march(ray_pos, self.key.pos) * weight;
+                light_weight += weight;
+                samples += cloud.num_steps_light;
+                transmittance *= beer(density * step_size * cloud.light_absorption_through_cloud);
+                if transmittance < 0.01 {
+                    break;
+                }
+            }
+            dst_travelled += step_size;
+        }
+
+        let col = match view {
+            CloudDebugView::Density if max_density <= 0.0 => Vec3::new(0.0, 0.0, 0.2),
+            CloudDebugView::Density => Vec3::splat(max_density / (1.0 + max_density)),
+            CloudDebugView::Transmittance => Vec3::splat(transmittance),
+            CloudDebugView::LightTransmittance if light_weight > 0.0 => {
+                Vec3::splat(light / light_weight)
+            }
+            CloudDebugView::Steps => {
+                let most = cloud.num_steps * (1 + cloud.num_steps_light);
+                heat(samples as f32 / most.max(1) as f32)
+            }
+            _ => Vec3::ZERO,
+        };
+        col.extend(1.0)
+    }
+}
+
+/// Blue to red false colour of `t` in `[0, 1]`
+fn heat(t: f32) -> Vec3 {
+    let t = t.clamp(0.0, 1.0);
+    Vec3::new(
+        (t * 2.0 - 0.5).clamp(0.0, 1.0),
+        1.0 - (t * 2.0 - 1.0).abs(),
+        (1.0 - t * 2.0).clamp(0.0, 1.0),
+    )
+}
+
+/// Splits the triangles that straddle the left and right edges of a panorama into a copy
+/// wrapped around each edge
+fn wrap_seam<A: Copy>(triangles: &mut Vec<RasterTriangle<A>>, width: f32) {
+    let half = width * 0.5;
+    let mut wrapped = Vec::new();
+    for triangle in triangles.iter_mut() {
+        let [v0, v1, v2] = triangle.screen;
+        if v0.x.max(v1.x).max(v2.x) - v0.x.min(v1.x).min(v2.x) <= half {
+            continue;
+        }
+        let mut right = *triangle;
+        for (l, r) in triangle.screen.iter_mut().zip(right.screen.iter_mut()) {
+            if l.x > half {
+                l.x -= width;
+            } else {
+                r.x += width;
+            }
+        }
+        wrapped.push(right);
+    }
+    triangles.extend(wrapped);
+}
+
+/// Camera rays through the points of a `width` x `height` frame
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PixelRays {
+    camera: Camera,
+    size: egui::Vec2,
+}
+
+impl PixelRays {
+    pub fn new(camera: &Camera, width: usize, height: usize) -> Self {
+        Self {
+            camera: *camera,
+            size: egui::Vec2::new(width as f32, height as f32),
+        }
+    }
+
+    /// Origin and normalised direction of the ray through the point `x`, `y` in pixels.
+    /// The ray of a whole pixel goes through its top left corner, see `Camera::ray`.
+    #[inline]
+    pub fn at(&self, x: f32, y: f32) -> (Vec3, Vec3) {
+        self.camera.ray(x, y, self.size.x, self.size.y)
+    }
+
+    /// Ray through the pixel in column `x` and row `y`
+    #[inline]
+    pub fn pixel(&self, x: usize, y: usize) -> (Vec3, Vec3) {
+        self.at(x as f32, y as f32)
+    }
+
+    /// Rays with their times for every offset from the pixel in column `x` and row `y`,
+    /// each split into the lens and shutter samples of the physical camera
+    pub fn samples(&self, x: usize, y: usize, offsets: &[glam::Vec2]) -> Vec<((Vec3, Vec3), f32)> {
+        let count = self.camera.physical.samples();
+        let (w, h) = (self.size.x, self.size.y);
+        offsets
+            .iter()
+            .enumerate()
+            .flat_map(|(cell, o)| {
+                let (px, py) = (x as f32 + o.x, y as f32 + o.y);
+                lens_samples(x, y, cell, count)
+                    .into_iter()
+                    .map(move |(lens, shutter)| self.camera.lens_ray(px, py, w, h, lens, shutter))
+            })
+            .collect()
+    }
+
+    /// Position in pixels of a world point, `None` when it is outside the clipping range
+    #[inline]
+    pub fn project(&self, point: Vec3) -> Option<Pos2> {
+        self.camera.project(point, self.size.x, self.size.y)
+    }
+}
+
+/// Shades the pixels between `min` and `max` into a separate framebuffer.
+/// `shade` returns the colour and the depth of a pixel given its column and row.
+pub(crate) fn shade_pixels(
+    min: [usize; 2],
+    max: [usize; 2],
+    shade: impl Fn(usize, usize) -> Option<(Vec4, f32)> + Sync,
+) -> Framebuffer {
+    use rayon::prelude::*;
+
+    let (w, h) = (max[0] - min[0], max[1] - min[1]);
+    let samples = (0..w * h)
+        .into_par_iter()
+        .map(|idx| shade(idx % w + min[0], idx / w + min[1]))
+        .collect::<Vec<_>>();
+    let mut img = Framebuffer::new(w, h);
+    for (idx, sample) in samples.into_iter().enumerate() {
+        if let Some((col, depth)) = sample {
+            img.set(idx % w, idx / w, col);
+            img.set_depth(idx % w, idx / w, depth);
+        }
+    }
+    img
+}
+
+/// Shades the pixels between `min` and `max` of a `width` x `height` frame into a separate
+/// framebuffer. `shade` returns the colour of a pixel given its view direction,
+/// the pixels are infinitely far away.
+pub(crate) fn shade_region(
+    camera: &Camera,
+    width: usize,
+    height: usize,
+    min: [usize; 2],
+    max: [usize; 2],
+    shade: impl Fn(Vec3) -> Option<Vec4> + Sync,
+) -> Framebuffer {
+    let rays = PixelRays::new(camera, width, height);
+    shade_pixels(min, max, |x, y| {
+        shade(rays.pixel(x, y).1).map(|col| (col, f32::INFINITY))
+    })
+}
+
+/// Sets the depth of the visible pixels of an image placed at `min` on the frame
+pub(crate) fn fill_depth(
+    img: &mut Framebuffer,
+    min: [usize; 2],
+    depth: impl Fn(usize, usize) -> f32,
+) {
+    for y in 0..img.height() {
+        for x in 0..img.width() {
+            if img.get(x, y).w > 0.0 {
+                img.set_depth(x, y, depth(x + min[0], y + min[1]));
+            }
+        }
+    }
+}
+
+/// Pixel bounds of a box on a `width` x `height` frame, the whole frame when a corner
+/// is outside the clipping range or the projection bends lines
+pub(crate) fn box_bounds(
+    camera: &Camera,
+    bb: &BoundingBox,
+    width: usize,
+    height: usize,
+) -> Option<([usize; 2], [usize; 2])> {
+    let whole = (width > 0 && height > 0).then_some(([0, 0], [width, height]));
+    if !camera.projector().preserves_lines() {
+        return whole;
+    }
+    let size = egui::Vec2::new(width as f32, height as f32);
+    let (mut min, mut max) = (size, egui::Vec2::ZERO);
+    for corner in bb.corners() {
+        let Some(p) = camera.project(corner, size.x, size.y) else {
+            return whole;
+        };
+        min = min.min(p.to_vec2());
+        max = max.max(p.to_vec2());
+    }
+    let min = min.floor().clamp(egui::Vec2::ZERO, size);
+    let max = max.ceil().clamp(egui::Vec2::ZERO, size);
+    (min.x < max.x && min.y < max.y).then_some((
+        [min.x as usize, min.y as usize],
+        [max.x as usize, max.y as usize],
+    ))
+}
+
+/// Pixel bounds of a disk of the given angular radius around `dir`, clipped to the image.
+/// Only the perspective projection gives a bounded disk, the others shade the whole frame.
+pub(crate) fn disk_bounds(
+    camera: &Camera,
+    dir: Vec3,
+    angular_radius: f32,
+    width: usize,
+    height: usize,
+) -> Option<([usize; 2], [usize; 2])> {
+    if camera.kind != ProjectionKind::Perspective {
+        return (width > 0 && height > 0).then_some(([0, 0], [width, height]));
+    }
+    let size = egui::Vec2::new(width as f32, height as f32);
+    let center = camera
+        .project(camera.pos() + dir, size.x, size.y)?
+        .to_vec2();
+
+    let radius_px = angular_radius.tan() / (camera.proj.fov * 0.5).tan() * size.y * 0.5;
+    let min = (center - egui::Vec2::splat(radius_px + 1.0)).floor();
+    let max = (center + egui::Vec2::splat(radius_px + 1.0)).ceil();
+    let min = min.max(egui::Vec2::ZERO);
+    let max = max.min(size);
+    (min.x < max.x && min.y < max.y).then_some((
+        [min.x as usize, min.y as usize],
+        [max.x as usize, max.y as usize],
+    ))
+}
@@ -0,0 +1,133 @@
+//! Timings and counters of a draw, to see which pass makes a frame slow
+
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Work of one draw, zero for the passes that did not run
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderStats {
+    pub total: Duration,
+    /// Sky, stars and the sun and moon disks
+    pub sky: Duration,
+    /// One entry per cloud in the order they are drawn
+    pub clouds: Vec<Duration>,
+    /// Terrain raster including its cloud shadows
+    pub terrain: Duration,
+    /// Cloud shadows on the terrain, summed over the worker threads
+    pub shadows: Duration,
+    /// Tone mapping and uploading the images to egui
+    pub upload: Duration,
+    /// Cloud rays marched this draw, cached pixels are not marched again
+    pub rays: u64,
+    /// Density samples along the cloud rays, without the light marches
+    pub steps: u64,
+    /// Rays stopped once the cloud became opaque
+    pub terminated_early: u64,
+    /// Pixels the clouds cover
+    pub covered_pixels: u64,
+}
+
+impl RenderStats {
+    pub fn steps_per_ray(&self) -> f32 {
+        self.steps as f32 / self.rays.max(1) as f32
+    }
+
+    /// Fraction of the rays stopped once the cloud became opaque
+    pub fn terminated_ratio(&self) -> f32 {
+        self.terminated_early as f32 / self.rays.max(1) as f32
+    }
+
+    /// Adds the counts of the marchers into the stats
+    pub(crate) fn record(&mut self, counters: &PassCounters) {
+        self.rays += counters.rays.load(Ordering::Relaxed);
+        self.steps += counters.steps.load(Ordering::Relaxed);
+        self.terminated_early += counters.terminated.load(Ordering::Relaxed);
+        self.shadows += Duration::from_nanos(counters.shadow_nanos.load(Ordering::Relaxed));
+        self.upload += counters.upload_time();
+    }
+}
+
+/// Stats of several views drawn together, the clouds are matched by their order
+impl AddAssign<&RenderStats> for RenderStats {
+    fn add_assign(&mut self, rhs: &RenderStats) {
+        self.total += rhs.total;
+        self.sky += rhs.sky;
+        if self.clouds.len() < rhs.clouds.len() {
+            self.clouds.resize(rhs.clouds.len(), Duration::ZERO);
+        }
+        for (cloud, time) in self.clouds.iter_mut().zip(&rhs.clouds) {
+            *cloud += *time;
+        }
+        self.terrain += rhs.terrain;
+        self.shadows += rhs.shadows;
+        self.upload += rhs.upload;
+        self.rays += rhs.rays;
+        self.steps += rhs.steps;
+        self.terminated_early += rhs.terminated_early;
+        self.covered_pixels += rhs.covered_pixels;
+    }
+}
+
+/// Counts shared by the threads of a pass
+#[derive(Debug, Default)]
+pub(crate) struct PassCounters {
+    rays: AtomicU64,
+    steps: AtomicU64,
+    terminated: AtomicU64,
+    shadow_nanos: AtomicU64,
+    upload_nanos: AtomicU64,
+}
+
+impl PassCounters {
+    pub fn ray(&self, steps: u64, terminated: bool) {
+        self.rays.fetch_add(1, Ordering::Relaxed);
+        self.steps.fetch_add(steps, Ordering::Relaxed);
+        if terminated {
+            self.terminated.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn shadow(&self, time: Duration) {
+        self.shadow_nanos
+            .fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn upload(&self, time: Duration) {
+        self.upload_nanos
+            .fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn upload_time(&self) -> Duration {
+        Duration::from_nanos(self.upload_nanos.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_of_views_add_up() {
+        let counters = PassCounters::default();
+        counters.ray(10, true);
+        counters.ray(20, false);
+        counters.shadow(Duration::from_millis(2));
+
+        let mut view = RenderStats {
+            clouds: vec![Duration::from_millis(3)],
+            ..Default::default()
+        };
+        view.record(&counters);
+        assert_eq!(view.steps_per_ray(), 15.0);
+        assert_eq!(view.terminated_ratio(), 0.5);
+        assert_eq!(view.shadows, Duration::from_millis(2));
+
+        let mut total = RenderStats::default();
+        total += &view;
+        total += &view;
+        assert_eq!(total.clouds, vec![Duration::from_millis(6)]);
+        assert_eq!(total.rays, 4);
+        assert_eq!(RenderStats::default().steps_per_ray(), 0.0);
+    }
+}
@@ -0,0 +1,344 @@
+//! Cloud layer that marches a sixteenth of the pixels per frame, reprojects the rest
+//! from the previous frame and accumulates the marched pixels into their history
+
+use glam::{Vec3, Vec4};
+
+use crate::canvas::Framebuffer;
+use crate::object::camera::{Camera, PhysicalCamera};
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::{Moon, Sky, Sun};
+
+/// Side of the pixel block in which one pixel is marched per frame
+const BLOCK: usize = 4;
+
+/// Order in which the pixels of a block are marched, a 4x4 Bayer matrix,
+/// so that consecutive frames refresh pixels far apart
+const BAYER: [usize; BLOCK * BLOCK] = [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5];
+
+/// Weight of a freshly marched colour against the history of its pixel
+const SAMPLE_WEIGHT: f32 = 0.5;
+
+/// What the history depends on besides the camera and the wind, which are reprojected
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudHistoryKey {
+    pub width: usize,
+    pub height: usize,
+    /// Depth of field and the shutter are not reprojected
+    pub physical: PhysicalCamera,
+    /// Cloud parameters with the wind offset and velocity zeroed
+    pub cloud: CloudBuilder,
+    pub sun: Option<Sun>,
+    pub moon: Option<Moon>,
+    pub sky: Option<Sky>,
+}
+
+impl CloudHistoryKey {
+    pub fn new(
+        width: usize,
+        height: usize,
+        physical: PhysicalCamera,
+        mut cloud: CloudBuilder,
+        sun: Option<Sun>,
+        moon: Option<Moon>,
+        sky: Option<Sky>,
+    ) -> Self {
+        cloud.offset = Vec3::ZERO;
+        cloud.wind = Vec3::ZERO;
+        Self {
+            width,
+            height,
+            physical,
+            cloud,
+            sun,
+            moon,
+            sky,
+        }
+    }
+}
+
+/// Camera and wind of a rendered frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CloudFrame {
+    pub camera: Camera,
+    pub offset: Vec3,
+}
+
+/// Previous cloud frame over the whole canvas and what it was rendered with
+#[derive(Debug, Default)]
+pub struct TemporalCloud {
+    key: Option<CloudHistoryKey>,
+    prev: Option<CloudFrame>,
+    history: Framebuffer,
+    frame: usize,
+}
+
+impl TemporalCloud {
+    /// The last frame, as large as the canvas
+    pub fn history(&self) -> &Framebuffer {
+        &self.history
+    }
+
+    /// Renders the pixels between `min` and `max` and keeps the whole frame as the new history.
+    ///
+    /// `locate` returns the point of the cloud seen through a pixel, `None` when the ray misses it;
+    /// `occluder` returns the distance to the nearest opaque surface along the pixel ray;
+    /// `drift` maps the wind offset of the previous frame to the world-space shift of the shapes;
+    /// `shade` marches a pixel.
+    ///
+    /// Marched pixels are blended into their history clamped to the range of the pixels marched
+    /// around them. History is rejected where a surface moved in front of the point or away from it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        key: CloudHistoryKey,
+        frame: CloudFrame,
+        min: [usize; 2],
+        max: [usize; 2],
+        drift: impl Fn(Vec3) -> Vec3,
+        locate: impl Fn(usize, usize) -> Option<Vec3> + Sync,
+        occluder: impl Fn(usize, usize) -> f32 + Sync,
+        shade: impl Fn(usize, usize) -> Vec4 + Sync,
+    ) -> &Framebuffer {
+        use rayon::prelude::*;
+
+        let (width, height) = (key.width, key.height);
+        let prev = match self.key.as_ref() == Some(&key) {
+            true => self.prev,
+            false => None,
+        };
+        let reproject = prev.map(|prev| (prev.camera, drift(prev.offset)));
+        let slot = self.frame % BAYER.len();
+        let (w, h) = (width as f32, height as f32);
+
+        // Reprojected history and freshly marched colour of every pixel
+        let history = &self.history;
+        let pixels = (0..width * height)
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                if !(min[0]..max[0]).contains(&x) || !(min[1]..max[1]).contains(&y) {
+                    return (None, None);
+                }
+                let Some(point) = locate(x, y) else {
+                    return (None, None);
+                };
+                let hidden = {
+                    let (origin, _) = frame.camera.ray(x as f32, y as f32, w, h);
+                    occluder(x, y) < point.distance(origin)
+                };
+                let reprojected = reproject
+                    .and_then(|(camera, drift)| sample(history, &camera, point - drift, hidden));
+                let refresh = BAYER[(y % BLOCK) * BLOCK + x % BLOCK] == slot;
+                let fresh = (refresh || reprojected.is_none()).then(|| shade(x, y));
+                (reprojected, fresh)
+            })
+            .collect::<Vec<_>>();
+
+        // Range of the marched colours in every block
+        let blocks = [width.div_ceil(BLOCK), height.div_ceil(BLOCK)];
+        let mut ranges = vec![None::<(Vec4, Vec4)>; blocks[0] * blocks[1]];
+        for (i, (_, fresh)) in pixels.iter().enumerate() {
+            if let Some(col) = fresh {
+                let block = (i / width / BLOCK) * blocks[0] + i % width / BLOCK;
+                let range = ranges[block].get_or_insert((*col, *col));
+                *range = (range.0.min(*col), range.1.max(*col));
+            }
+        }
+
+        let mut current = Framebuffer::new(width, height);
+        for (i, (reprojected, fresh)) in pixels.into_iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let col = match (reprojected, fresh) {
+                (Some(history), Some(col)) => {
+                    let (bx, by) = (x / BLOCK, y / BLOCK);
+                    let neighbourhood = (by.saturating_sub(1)..(by + 2).min(blocks[1]))
+                        .flat_map(|by| {
+                            (bx.saturating_sub(1)..(bx + 2).min(blocks[0]))
+                                .map(move |bx| by * blocks[0] + bx)
+                        })
+                        .filter_map(|block| ranges[block])
+                        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)));
+                    let history = neighbourhood.map_or(history, |(lo, hi)| history.clamp(lo, hi));
+                    history.lerp(col, SAMPLE_WEIGHT)
+                }
+                (Some(history), None) => history,
+                (None, Some(col)) => col,
+                (None, None) => continue,
+            };
+            current.set(x, y, col);
+        }
+        for y in min[1]..max[1] {
+            for x in min[0]..max[0] {
+                current.set_depth(x, y, occluder(x, y));
+            }
+        }
+
+        self.history = current;
+        self.key = Some(key);
+        self.prev = Some(frame);
+        self.frame += 1;
+        &self.history
+    }
+}
+
+/// Bilinearly samples `history` where `point` was on the previous frame, `None` when it was
+/// off screen or when a surface covered it on one of the frames and not on the other.
+/// The depth of the history is the distance to the surfaces in front of the cloud.
+fn sample(history: &Framebuffer, camera: &Camera, point: Vec3, hidden: bool) -> Option<Vec4> {
+    let (w, h) = (history.width() as f32, history.height() as f32);
+    // Rays go through the pixel corners, see `Camera::ray`
+    let sc = camera.project(point, w, h)?;
+    let (x, y) = (sc.x, sc.y);
+    if x < 0.0 || y < 0.0 || x > w - 1.0 || y > h - 1.0 {
+        return None;
+    }
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = (
+        (x0 + 1).min(history.width() - 1),
+        (y0 + 1).min(history.height() - 1),
+    );
+    let (origin, _) = camera.ray(x, y, w, h);
+    let was_hidden = history.depth(x.round() as usize, y.round() as usize) < point.distance(origin);
+    if was_hidden != hidden {
+        return None;
+    }
+    let (fx, fy) = (x.fract(), y.fract());
+    let top = history.get(x0, y0).lerp(history.get(x1, y0), fx);
+    let bottom = history.get(x0, y1).lerp(history.get(x1, y1), fx);
+    Some(top.lerp(bottom, fy))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::object::camera::{ArcBall, Orthographic, ProjectionKind};
+
+    const W: usize = 16;
+    const H: usize = 8;
+
+    fn key() -> CloudHistoryKey {
+        CloudHistoryKey::new(
+            W,
+            H,
+            PhysicalCamera::default(),
+            CloudBuilder::default(),
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Orthographic view of the z = 0 plane, one world unit per pixel
+    fn camera() -> Camera {
+        Camera {
+            kind: ProjectionKind::Orthographic,
+            ortho: Orthographic {
+                height: H as f32,
+                ..Default::default()
+            },
+            view: ArcBall {
+                pivot: Vec3::ZERO,
+                distance: 10.0,
+                yaw: std::f32::consts::FRAC_PI_2,
+                pitch: 0.0,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn frame(camera: Camera) -> CloudFrame {
+        CloudFrame {
+            camera,
+            offset: Vec3::ZERO,
+        }
+    }
+
+    fn locate(camera: Camera) -> impl Fn(usize, usize) -> Option<Vec3> + Sync {
+        move |x, y| {
+            let (origin, dir) = camera.ray(x as f32, y as f32, W as f32, H as f32);
+            Some(origin + dir * 10.0)
+        }
+    }
+
+    #[test]
+    fn test_reprojects_all_but_one_pixel_per_block() {
+        let camera = camera();
+        let marched = AtomicUsize::new(0);
+        let shade = |_, _| {
+            marched.fetch_add(1, Ordering::Relaxed);
+            Vec4::ONE
+        };
+        let unoccluded = |_, _| f32::INFINITY;
+
+        let mut cloud = TemporalCloud::default();
+        let (min, max) = ([0, 0], [W, H]);
+        let drift = |_| Vec3::ZERO;
+        cloud.render(
+            key(),
+            frame(camera),
+            min,
+            max,
+            drift,
+            locate(camera),
+            unoccluded,
+            shade,
+        );
+        assert_eq!(marched.swap(0, Ordering::Relaxed), W * H);
+
+        let fb = cloud.render(
+            key(),
+            frame(camera),
+            min,
+            max,
+            drift,
+            locate(camera),
+            unoccluded,
+            shade,
+        );
+        assert_eq!(fb.get(5, 3), Vec4::ONE);
+        assert_eq!(marched.load(Ordering::Relaxed), W * H / (BLOCK * BLOCK));
+    }
+
+    #[test]
+    fn test_reprojection_follows_the_camera() {
+        // The colour is the world position, so that every reprojected pixel can be checked
+        let colour = |p: Vec3| p.extend(1.0);
+        let (min, max) = ([0, 0], [W, H]);
+        let drift = |_| Vec3::ZERO;
+        let unoccluded = |_, _| f32::INFINITY;
+
+        let first = camera();
+        let mut cloud = TemporalCloud::default();
+        let at = locate(first);
+        let shade = |x, y| colour(at(x, y).unwrap());
+        cloud.render(key(), frame(first), min, max, drift, &at, unoccluded, shade);
+
+        let mut moved = first;
+        moved.translate(moved.basis()[0] * 3.0);
+        let at = locate(moved);
+        let shade = |x, y| colour(at(x, y).unwrap());
+        let fb = cloud.render(key(), frame(moved), min, max, drift, &at, unoccluded, shade);
+        for y in 0..H {
+            for x in 0..W {
+                let expected = colour(at(x, y).unwrap());
+                assert!(fb.get(x, y).abs_diff_eq(expected, 1e-4), "{x} {y}");
+            }
+        }
+
+        // A surface moves in front of the left half, its history is not reused
+        let occluded = |x, _| if x < W / 2 { 1.0 } else { f32::INFINITY };
+        let shade = |_, _| Vec4::splat(7.0);
+        let fb = cloud.render(key(), frame(moved), min, max, drift, &at, occluded, shade);
+        let refreshed = |x: usize, y: usize| BAYER[(y % BLOCK) * BLOCK + x % BLOCK] == 2;
+        for y in 0..H {
+            for x in (0..W).filter(|&x| !refreshed(x, y)) {
+                match x < W / 2 {
+                    true => assert_eq!(fb.get(x, y), Vec4::splat(7.0), "{x} {y}"),
+                    false => assert!(fb.get(x, y).abs_diff_eq(colour(at(x, y).unwrap()), 1e-4)),
+                }
+            }
+        }
+    }
+}
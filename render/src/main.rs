@@ -0,0 +1,211 @@
+mod settings;
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use domain::canvas::{Framebuffer, ToneMapping};
+use domain::facade::{CameraCommand, DrawCommand, Executor, Facade, SceneCommand};
+use domain::object::camera::Camera;
+use domain::object::Component;
+use domain::scene::description::SceneDescription;
+use image::ImageFormat;
+use log::info;
+
+use crate::settings::RenderSettings;
+
+const USAGE: &str = "\
+Usage: render --scene <scene.json> [options]
+
+Options:
+    --scene <file>        scene description
+    --camera <file>       camera, the default camera when omitted
+    --bookmark <name>     camera bookmark of the scene, overrides --camera
+    --settings <file>     render settings, the defaults when omitted
+    --size <w>x<h>        output resolution, e.g. 3840x2160, overrides the settings
+    --frames <a>..<b>     inclusive frame range, overrides the settings
+    --threads <n>         worker threads, overrides the settings
+    --output <path>       output path with an optional {frame} placeholder, .png or .hdr
+    --help                print this message";
+
+#[derive(Default)]
+struct Args {
+    scene: Option<PathBuf>,
+    camera: Option<PathBuf>,
+    bookmark: Option<String>,
+    settings: Option<PathBuf>,
+    size: Option<(usize, usize)>,
+    frames: Option<(u32, u32)>,
+    threads: Option<usize>,
+    output: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn std::error::Error>> {
+    let mut args = Args::default();
+    let mut it = std::env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or_else(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--scene" => args.scene = Some(value()?.into()),
+            "--camera" => args.camera = Some(value()?.into()),
+            "--bookmark" => args.bookmark = Some(value()?),
+            "--settings" => args.settings = Some(value()?.into()),
+            "--output" => args.output = Some(value()?.into()),
+            "--threads" => args.threads = Some(value()?.parse()?),
+            "--size" => {
+                let size = value()?;
+                let (w, h) = size
+                    .split_once('x')
+                    .ok_or_else(|| format!("expected <w>x<h>, got {size}"))?;
+                args.size = Some((w.parse()?, h.parse()?));
+            }
+            "--frames" => {
+                let range = value()?;
+                args.frames = Some(match range.split_once("..") {
+                    Some((a, b)) => (a.parse()?, b.parse()?),
+                    None => (range.parse()?, range.parse()?),
+                });
+            }
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {arg}\n\n{USAGE}").into()),
+        }
+    }
+    Ok(args)
+}
+
+fn save_frame(
+    framebuffer: &Framebuffer,
+    tone_mapping: &ToneMapping,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let (width, height) = (framebuffer.width() as u32, framebuffer.height() as u32);
+    match ImageFormat::from_path(path)? {
+        ImageFormat::Png => image::save_buffer_with_format(
+            path,
+            &framebuffer.to_rgba8(tone_mapping),
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+            ImageFormat::Png,
+        )?,
+        ImageFormat::Hdr => {
+            let pixels = framebuffer
+                .to_linear_rgb(tone_mapping)
+                .into_iter()
+                .flat_map(|p| p.to_array())
+                .collect();
+            image::Rgb32FImage::from_raw(width, height, pixels)
+                .ok_or("framebuffer size mismatch")?
+                .save_with_format(path, ImageFormat::Hdr)?
+        }
+        format => return Err(format!("unsupported output format {format:?}").into()),
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+
+    let args = parse_args()?;
+    let scene_path = args
+        .scene
+        .ok_or_else(|| format!("--scene is required\n\n{USAGE}"))?;
+    let scene: SceneDescription = settings::load(&scene_path)?;
+    let camera: Camera = match (&args.bookmark, &args.camera) {
+        (Some(name), _) => *scene
+            .bookmarks
+            .get(name)
+            .ok_or_else(|| format!("the scene has no camera bookmark {name}"))?,
+        (None, Some(path)) => settings::load(path)?,
+        (None, None) => Camera::default(),
+    };
+    let mut settings: RenderSettings = match &args.settings {
+        Some(path) => settings::load(path)?,
+        None => RenderSettings::default(),
+    };
+    if let Some((width, height)) = args.size {
+        settings.width = width;
+        settings.height = height;
+    }
+    if let Some((start, end)) = args.frames {
+        settings.frame_start = start;
+        settings.frame_end = end;
+    }
+    if let Some(threads) = args.threads {
+        settings.threads = threads;
+    }
+    if let Some(output) = args.output {
+        settings.output = output;
+    }
+    if settings.width == 0 || settings.height == 0 {
+        return Err("the output resolution must not be empty".into());
+    }
+    if settings.frame_start > settings.frame_end {
+        return Err("the first frame is after the last one".into());
+    }
+    if !(settings.seconds_per_frame.is_finite() && settings.seconds_per_frame > 0.0) {
+        return Err("seconds_per_frame must be a positive number".into());
+    }
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(settings.threads)
+        .build_global()?;
+
+    let mut executor = Facade::default();
+    let mut clouds = Vec::new();
+    let mut suns = Vec::new();
+    for (name, object) in scene.build() {
+        match &object {
+            Component::Cloud(cloud) => clouds.push((name, cloud.offset)),
+            Component::Sun(_) => suns.push(name),
+            _ => {}
+        }
+        executor.exec(SceneCommand::AddObject(name, object));
+    }
+    executor.exec(CameraCommand::SetCamera(camera));
+    executor.exec(DrawCommand::SetSupersampling(settings.supersampling));
+    executor.exec(DrawCommand::SetCameraRig(settings.rig));
+    // The shutter of the physical camera blurs the clouds along their movement
+    let wind = settings.cloud_offset_per_frame / settings.seconds_per_frame;
+    for &(name, _) in &clouds {
+        executor.exec(SceneCommand::SetWind(name, wind));
+    }
+
+    for frame in settings.frame_start..=settings.frame_end {
+        let start = Instant::now();
+        for &(name, offset) in &clouds {
+            let offset = offset + settings.cloud_offset_per_frame * frame as f32;
+            executor.exec(SceneCommand::SetOffset(name, offset));
+        }
+        let frame_camera = settings.camera(camera, frame).unwrap_or(camera);
+        executor.exec(CameraCommand::SetCamera(frame_camera));
+        if let Some(time) = settings.time_of_day(frame) {
+            for &name in &suns {
+                executor.exec(SceneCommand::SetSunTimeOfDay(name, time));
+            }
+        }
+
+        let framebuffer = executor
+            .exec(DrawCommand::RenderImage(settings.width, settings.height))
+            .into_image()
+            .ok_or("the renderer returned no image")?;
+        let path = settings.frame_path(frame);
+        let tone_mapping = settings.tone_mapping.for_camera(&frame_camera);
+        save_frame(&framebuffer, &tone_mapping, &path)?;
+        info!(
+            "frame {frame} -> {} in {:.2?}",
+            path.display(),
+            start.elapsed()
+        );
+    }
+    Ok(())
+}
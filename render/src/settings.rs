@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+
+use domain::canvas::ToneMapping;
+use domain::math::TimeOfDay;
+use domain::object::camera::Camera;
+use domain::object::camera_path::CameraPath;
+use domain::object::camera_rig::CameraRig;
+use domain::visitor::sampling::Supersampling;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Output resolution, frames and animation of a batch render
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Size of every view, the frame grows with the layout of the rig
+    pub width: usize,
+    pub height: usize,
+    /// First frame, inclusive
+    pub frame_start: u32,
+    /// Last frame, inclusive
+    pub frame_end: u32,
+    /// Worker threads, 0 lets rayon decide
+    pub threads: usize,
+    /// Output path, `{frame}` is replaced with the zero-padded frame number.
+    /// The extension selects the format: `.png` or `.hdr`.
+    pub output: PathBuf,
+    /// Added to the offset of every cloud each frame
+    pub cloud_offset_per_frame: Vec3,
+    /// Positions every sun from this clock when set
+    pub time_of_day: Option<TimeOfDay>,
+    /// Clock advance per frame in hours
+    pub hours_per_frame: f32,
+    /// Applied to `.png` frames, `.hdr` frames only get the exposure
+    pub tone_mapping: ToneMapping,
+    pub supersampling: Supersampling,
+    /// Flies the camera along the keyframes when set
+    pub camera_path: Option<CameraPath>,
+    /// Animation time per frame in seconds, the clock of the camera path
+    pub seconds_per_frame: f32,
+    /// Stereo pair or grid of views written side by side into every frame
+    pub rig: CameraRig,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 1056,
+            height: 900,
+            frame_start: 0,
+            frame_end: 0,
+            threads: 0,
+            output: PathBuf::from("frame_{frame}.png"),
+            cloud_offset_per_frame: Vec3::ZERO,
+            time_of_day: None,
+            hours_per_frame: 0.0,
+            tone_mapping: ToneMapping::default(),
+            supersampling: Supersampling::default(),
+            camera_path: None,
+            seconds_per_frame: 1.0 / 24.0,
+            rig: CameraRig::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        let path = self.output.to_string_lossy();
+        PathBuf::from(path.replace("{frame}", &format!("{frame:04}")))
+    }
+
+    /// Clock of the given frame, `None` when the sun is not animated
+    pub fn time_of_day(&self, frame: u32) -> Option<TimeOfDay> {
+        self.time_of_day.map(|mut time| {
+            time.advance(self.hours_per_frame * frame as f32);
+            time
+        })
+    }
+
+    /// `camera` on the path at the given frame, `None` when the camera is not animated
+    pub fn camera(&self, camera: Camera, frame: u32) -> Option<Camera> {
+        let path = self.camera_path.as_ref()?;
+        Some(path.camera(camera, self.seconds_per_frame * frame as f32))
+    }
+}
+
+pub fn load<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let value = serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(value)
+}
@@ -1,22 +1,30 @@
 use egui::Color32;
-use glam::{Vec3, Vec4, Vec4Swizzles};
+use glam::{Vec3, Vec4};
 
+use domain::canvas::color;
 use domain::object::camera::Camera;
 use domain::object::objects::{Cloud, Sun};
 use domain::object::objects::cloud::{beer, hg, phase, CloudBuilder};
 use domain::object::objects::texture3d::{PerlinBuilder, WorleyBuilder};
+use domain::scene::scene::Scene;
+use domain::visitor::image_renderer::ImageRenderer;
+use domain::visitor::sampling::{SamplePattern, Supersampling};
 use domain::visitor::{Visitable, Visitor};
 
 pub struct DrawVisitorTest<'a> {
     camera: &'a Camera,
     sun: &'a Sun,
+    width: usize,
+    height: usize,
 }
 
 impl<'a> DrawVisitorTest<'a> {
-    pub fn new(camera: &'a Camera, sun: &'a Sun) -> Self {
+    pub fn new(camera: &'a Camera, sun: &'a Sun, width: usize, height: usize) -> Self {
         Self {
             camera,
-            sun
+            sun,
+            width,
+            height,
         }
     }
 }
@@ -27,17 +35,12 @@ impl<'a> Visitor for DrawVisitorTest<'a> {
         use rayon::prelude::*;
 
         let bb = cloud.bounding_box();
-        let (w, h) = (1056, 900);
+        let (w, h) = (self.width, self.height);
         
         let sun_pos = self.sun.get_pos();
-        let light_color: Vec4 = cloud
-            .light_color
-            .to_array()
-            .map(|x| x as f32 / 255.0)
-            .into();
+        let light_color = self.sun.light().radiance;
 
         let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
-        let ray_origin = self.camera.pos();
         img.pixels
             .par_iter_mut()
             .enumerate()
@@ -45,7 +48,7 @@ impl<'a> Visitor for DrawVisitorTest<'a> {
                 let i = idx / w;
                 let j = idx % w;
 
-                let ray_dir = (self.camera.egui_to_world(i, j, 1056, 900) - ray_origin).normalize();
+                let (ray_origin, ray_dir) = self.camera.ray(j as f32, i as f32, w as f32, h as f32);
 
                 let ray_box_info = bb.dst(ray_origin, ray_dir);
                 let dst_to_box = ray_box_info.x;
@@ -88,16 +91,10 @@ impl<'a> Visitor for DrawVisitorTest<'a> {
                             .powf(cloud.params.x);
                         let sun = hg(focused_eye_cos, 0.995).clamp(-1.0, 1.0) * transmittance;
 
-                        let cloud_col = light_energy * light_color.xyz();
+                        let cloud_col = light_energy * light_color;
                         let col = cloud_col.clamp(Vec3::ZERO, Vec3::ONE) * (1.0 - sun)
-                            + light_color.xyz() * sun;
-                        let (r, g, b) = col.into();
-                        Color32::from_rgba_unmultiplied(
-                            (r * 255.0) as u8,
-                            (g * 255.0) as u8,
-                            (b * 255.0) as u8,
-                            255,
-                        )
+                            + light_color * sun;
+                        color::to_color32(col.extend(1.0))
                     };
                 }
             });
@@ -108,14 +105,18 @@ impl<'a> Visitor for DrawVisitorTest<'a> {
 pub struct DrawVisitorTest2<'a> {
     camera: &'a Camera,
     sun: &'a Sun,
+    width: usize,
+    height: usize,
 }
 
 
 impl<'a> DrawVisitorTest2<'a> {
-    pub fn new(camera: &'a Camera, sun: &'a Sun) -> Self {
+    pub fn new(camera: &'a Camera, sun: &'a Sun, width: usize, height: usize) -> Self {
         Self {
             camera,
-            sun
+            sun,
+            width,
+            height,
         }
     }
 }
@@ -126,17 +127,12 @@ impl<'a> Visitor for DrawVisitorTest2<'a> {
         use rayon::prelude::*;
 
         let bb = cloud.bounding_box();
-        let (w, h) = (1056, 900);
+        let (w, h) = (self.width, self.height);
 
         let sun_pos = self.sun.get_pos();
-        let light_color: Vec4 = cloud
-            .light_color
-            .to_array()
-            .map(|x| x as f32 / 255.0)
-            .into();
+        let light_color = self.sun.light().radiance;
 
         let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
-        let ray_origin = self.camera.pos();
         img.pixels
             .iter_mut()
             .enumerate()
@@ -144,7 +140,7 @@ impl<'a> Visitor for DrawVisitorTest2<'a> {
                 let i = idx / w;
                 let j = idx % w;
 
-                let ray_dir = (self.camera.egui_to_world(i, j, 1056, 900) - ray_origin).normalize();
+                let (ray_origin, ray_dir) = self.camera.ray(j as f32, i as f32, w as f32, h as f32);
 
                 let ray_box_info = bb.dst(ray_origin, ray_dir);
                 let dst_to_box = ray_box_info.x;
@@ -187,16 +183,10 @@ impl<'a> Visitor for DrawVisitorTest2<'a> {
                             .powf(cloud.params.x);
                         let sun = hg(focused_eye_cos, 0.995).clamp(-1.0, 1.0) * transmittance;
 
-                        let cloud_col = light_energy * light_color.xyz();
+                        let cloud_col = light_energy * light_color;
                         let col = cloud_col.clamp(Vec3::ZERO, Vec3::ONE) * (1.0 - sun)
-                            + light_color.xyz() * sun;
-                        let (r, g, b) = col.into();
-                        Color32::from_rgba_unmultiplied(
-                            (r * 255.0) as u8,
-                            (g * 255.0) as u8,
-                            (b * 255.0) as u8,
-                            255,
-                        )
+                            + light_color * sun;
+                        color::to_color32(col.extend(1.0))
                     };
                 }
             });
@@ -266,9 +256,15 @@ fn main() {
     
     let camera = Camera::default();
 
+    // Frame size in pixels: `research [width] [height]`
+    let mut args = std::env::args().skip(1).map(|x| x.parse::<usize>().ok());
+    let width = args.next().flatten().unwrap_or(1056);
+    let height = args.next().flatten().unwrap_or(900);
+    println!("frame: {}x{}", width, height);
+
     let sun = Sun::new(10.0, -135.0, -90.0);
-    let mut visitor = DrawVisitorTest::new(&camera, &sun);
-    let mut visitor2 = DrawVisitorTest2::new(&camera, &sun);
+    let mut visitor = DrawVisitorTest::new(&camera, &sun, width, height);
+    let mut visitor2 = DrawVisitorTest2::new(&camera, &sun, width, height);
     
     let mut cloud = cloud_params.build();
 
@@ -292,5 +288,24 @@ fn main() {
         // println!("2: steps: {}, time: {:?}", i, elapsed_time);
         
     }
+
+    // Cost of supersampling a whole frame with the offscreen renderer
+    let mut scene = Scene::default();
+    scene.add_object("sun", sun);
+    scene.add_object("cloud", cloud_params.build());
+    for pattern in SamplePattern::ALL {
+        for grid in 1..=3 {
+            let supersampling = Supersampling::new(pattern, grid);
+            let start_time = Instant::now();
+            let nums = 3;
+            for _ in 0..nums {
+                let mut renderer =
+                    ImageRenderer::new(&camera, width, height).with_supersampling(supersampling);
+                scene.accept(&mut renderer);
+            }
+            let elapsed_time = start_time.elapsed() / nums;
+            println!("ssaa: {:?} {}x{}, time: {:?}", pattern, grid, grid, elapsed_time);
+        }
+    }
     
 }
\ No newline at end of file
@@ -2,23 +2,48 @@ use eframe::egui;
 use eframe::egui::Color32;
 
 use domain::canvas::painter::Painter3D;
+use domain::canvas::{ToneMapOperator, ToneMapping};
 use domain::facade::{CameraCommand, DrawCommand, SceneCommand};
 use domain::facade::{Executor, Facade};
 use domain::math::transform::glam;
 use domain::math::transform::glam::{Vec3, Vec4};
-use domain::object::camera::Camera;
-use domain::object::objects::{Grid, Sun};
+use domain::math::TimeOfDay;
+use domain::object::camera::{Camera, ControllerKind, PhysicalCamera, ProjectionKind};
+use domain::object::camera_rig::CameraRig;
 use domain::object::objects::cloud::CloudBuilder;
+use domain::object::objects::sky::SkyBuilder;
 use domain::object::objects::terrain::TerrainBuilder;
 use domain::object::objects::texture3d::{NoiseBuilder, PerlinBuilder, WorleyBuilder};
+use domain::object::objects::textures::slice::{
+    CloudTexture, NoiseSlice, SlicePlane, HISTOGRAM_BINS,
+};
+use domain::object::objects::{Grid, Moon, Sun};
+use domain::scene::description::SceneDescription;
+use domain::visitor::draw_visitor::{CloudDebugView, CloudRenderMode, TerrainDebug};
+use domain::visitor::sampling::{SamplePattern, Supersampling};
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         ctx.set_visuals(egui::Visuals::light());
         ctx.request_repaint();
+        self.executor
+            .exec(CameraCommand::Update(ctx.input(|i| i.stable_dt)));
         self.cloud.offset += self.offset_speed;
         self.executor
             .exec(SceneCommand::SetOffset("cloud", self.cloud.offset));
+        let dt = ctx.input(|i| i.stable_dt).max(1e-3);
+        self.executor
+            .exec(SceneCommand::SetWind("cloud", self.offset_speed / dt));
+        if self.animate_day {
+            self.time_of_day
+                .advance(self.day_speed * ctx.input(|i| i.stable_dt));
+            self.set_time_of_day();
+        }
+        egui::SidePanel::right("control")
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| self.control(ui));
+            });
         egui::CentralPanel::default().show(ctx, |ui| {
             self.ui(ui);
         });
@@ -27,22 +52,55 @@ impl eframe::App for App {
 }
 
 impl App {
+    /// The viewport takes whatever space the control panel leaves
     fn ui(&mut self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            ui.vertical(|ui| {
-                ui.set_width(1024.0);
-                ui.set_height(800.0);
-                let (resp, painter) = self.painter(ui);
-                self.executor.exec(DrawCommand::SetPainter(painter));
-                self.handle_camera(&resp, ui);
+        let (resp, painter) = self.painter(ui);
+        self.executor.exec(DrawCommand::SetPainter(painter));
+        self.handle_camera(&resp, ui);
+        if self.show_stats {
+            self.stats_overlay(ui, resp.rect);
+        }
+    }
+
+    /// Timings of the previous frame in the corner of the viewport
+    fn stats_overlay(&mut self, ui: &mut egui::Ui, rect: egui::Rect) {
+        let Some(stats) = self.executor.exec(DrawCommand::GetStats).into_stats() else {
+            return;
+        };
+        let ms = |time: std::time::Duration| time.as_secs_f64() * 1000.0;
+        egui::Area::new(egui::Id::new("render_stats"))
+            .fixed_pos(rect.min + egui::vec2(8.0, 8.0))
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("Кадр: {:.1} мс", ms(stats.total)));
+                    ui.label(format!("Небо: {:.1} мс", ms(stats.sky)));
+                    for (i, time) in stats.clouds.iter().enumerate() {
+                        ui.label(format!("Облако {}: {:.1} мс", i + 1, ms(*time)));
+                    }
+                    ui.label(format!("Ландшафт: {:.1} мс", ms(stats.terrain)));
+                    ui.label(format!(
+                        "Тени на ландшафте: {:.1} мс суммарно по потокам",
+                        ms(stats.shadows)
+                    ));
+                    ui.label(format!("Загрузка текстур: {:.1} мс", ms(stats.upload)));
+                    ui.label(format!(
+                        "Лучей: {}, шагов на луч: {:.1}",
+                        stats.rays,
+                        stats.steps_per_ray()
+                    ));
+                    ui.label(format!(
+                        "Остановлено раньше: {:.0}%",
+                        stats.terminated_ratio() * 100.0
+                    ));
+                    ui.label(format!("Пикселей облаков: {}", stats.covered_pixels));
+                });
             });
-            self.control(ui);
-        });
     }
 
     fn painter(&mut self, ui: &mut egui::Ui) -> (egui::Response, Painter3D) {
         let (response, painter) =
-            ui.allocate_painter([1056.0, 900.0].into(), egui::Sense::click_and_drag());
+            ui.allocate_painter(ui.available_size(), egui::Sense::click_and_drag());
 
         let bc = self.background_color;
         painter.rect(
@@ -55,6 +113,14 @@ impl App {
         (response, Painter3D::new(painter, rect, bc))
     }
 
+    fn set_time_of_day(&mut self) {
+        let position = self.time_of_day.solar_position();
+        self.sun.1 = position.elevation;
+        self.sun.2 = position.azimuth;
+        self.executor
+            .exec(SceneCommand::SetSunTimeOfDay("sun", self.time_of_day));
+    }
+
     fn handle_camera(&mut self, resp: &egui::Response, ui: &mut egui::Ui) {
         if resp.dragged_by(egui::PointerButton::Primary) {
             if ui.input(|i| i.raw.modifiers.shift_only()) {
@@ -75,23 +141,348 @@ impl App {
             let zoom = CameraCommand::Zoom(ui.input(|i| -i.raw_scroll_delta.y));
             self.executor.exec(zoom);
         }
+
+        if self.controller == ControllerKind::Fly && !ui.ctx().wants_keyboard_input() {
+            let (direction, dt) = ui.input(|i| {
+                let axis = |pos, neg| i.key_down(pos) as i32 as f32 - i.key_down(neg) as i32 as f32;
+                let direction = Vec3::new(
+                    axis(egui::Key::D, egui::Key::A),
+                    axis(egui::Key::E, egui::Key::Q),
+                    axis(egui::Key::W, egui::Key::S),
+                );
+                (direction, i.stable_dt)
+            });
+            if direction != Vec3::ZERO {
+                self.executor.exec(CameraCommand::Fly(direction, dt));
+            }
+        }
+    }
+
+    /// Stereo pair or grid of views drawn side by side
+    fn camera_rig(&mut self, ui: &mut egui::Ui) {
+        let rig = self.rig;
+        let spacing = self.rig_spacing;
+        ui.horizontal(|ui| {
+            for (option, label) in [
+                (CameraRig::Mono, "Один"),
+                (
+                    CameraRig::Stereo {
+                        eye_separation: spacing,
+                    },
+                    "Стерео",
+                ),
+                (
+                    CameraRig::Grid {
+                        columns: 2,
+                        rows: 2,
+                        spacing,
+                    },
+                    "Сетка 2×2",
+                ),
+            ] {
+                ui.radio_value(&mut self.rig, option, label);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::widgets::Slider::new(&mut self.rig_spacing, 0.0..=5.0));
+            ui.label("Расстояние между камерами");
+        });
+        match &mut self.rig {
+            CameraRig::Mono => {}
+            CameraRig::Stereo { eye_separation } => *eye_separation = self.rig_spacing,
+            CameraRig::Grid { spacing, .. } => *spacing = self.rig_spacing,
+        }
+        if rig != self.rig {
+            self.executor.exec(DrawCommand::SetCameraRig(self.rig));
+        }
+    }
+
+    /// Lens and shutter of the camera, they only blur the clouds
+    fn physical_camera(&mut self, ui: &mut egui::Ui) {
+        let physical = self.physical;
+        ui.checkbox(&mut self.physical.enabled, "Включена");
+        ui.add_enabled_ui(self.physical.enabled, |ui| {
+            let slider = |ui: &mut egui::Ui, value: &mut f32, range, label| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::widgets::Slider::new(value, range).logarithmic(true));
+                    ui.label(label);
+                });
+            };
+            slider(
+                ui,
+                &mut self.physical.f_number,
+                1.0..=22.0,
+                "Диафрагменное число",
+            );
+            slider(
+                ui,
+                &mut self.physical.focal_length,
+                0.05..=5.0,
+                "Фокусное расстояние",
+            );
+            slider(
+                ui,
+                &mut self.physical.focus_distance,
+                0.5..=200.0,
+                "Дистанция фокусировки",
+            );
+            slider(ui, &mut self.physical.shutter, 1e-3..=1.0, "Выдержка, с");
+            slider(ui, &mut self.physical.iso, 50.0..=6400.0, "ISO");
+            ui.horizontal(|ui| {
+                ui.add(egui::widgets::Slider::new(
+                    &mut self.physical.samples,
+                    1..=32,
+                ));
+                ui.label("Лучей на пиксель");
+            });
+            ui.label(format!(
+                "Экспозиция {:+.2} ступени",
+                self.physical.exposure()
+            ));
+        });
+        if physical != self.physical {
+            self.executor
+                .exec(CameraCommand::SetPhysical(self.physical));
+        }
+    }
+
+    /// Saved viewpoints, kept in a scene file, and framing of the scene objects
+    fn camera_bookmarks(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Файл сцены");
+            ui.text_edit_singleline(&mut self.scene_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Загрузить").clicked() {
+                self.bookmark_error = self.load_bookmarks().err().map(|err| err.to_string());
+            }
+            if ui.button("Записать").clicked() {
+                self.bookmark_error = self.save_bookmarks().err().map(|err| err.to_string());
+            }
+        });
+        if let Some(err) = &self.bookmark_error {
+            ui.colored_label(Color32::RED, err);
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.bookmark_name);
+            let name = self.bookmark_name.trim().to_owned();
+            if ui.button("Сохранить").clicked() && !name.is_empty() {
+                self.executor.exec(CameraCommand::SaveBookmark(name));
+            }
+        });
+        let bookmarks = self
+            .executor
+            .exec(CameraCommand::GetBookmarks)
+            .into_bookmarks()
+            .unwrap_or_default();
+        let mut removed = None;
+        for (name, camera) in &bookmarks {
+            ui.horizontal(|ui| {
+                if ui.button(name).clicked() {
+                    self.executor
+                        .exec(CameraCommand::RecallBookmark(name.clone()));
+                    self.projection = camera.kind;
+                    self.controller = camera.controller;
+                }
+                if ui.button("Удалить").clicked() {
+                    removed = Some(name.clone());
+                }
+            });
+        }
+        if let Some(name) = removed {
+            self.executor.exec(CameraCommand::RemoveBookmark(name));
+        }
+        ui.horizontal(|ui| {
+            ui.label("Показать");
+            for (name, label) in [("cloud", "Облака"), ("terrain", "Ландшафт")] {
+                if ui.button(label).clicked() {
+                    self.executor.exec(CameraCommand::FrameObject(name));
+                    self.controller = ControllerKind::ArcBall;
+                }
+            }
+        });
+    }
+
+    /// Replaces the bookmarks with the ones stored in the scene file
+    fn load_bookmarks(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let scene: SceneDescription =
+            serde_json::from_str(&std::fs::read_to_string(&self.scene_path)?)?;
+        self.executor
+            .exec(CameraCommand::SetBookmarks(scene.bookmarks));
+        Ok(())
+    }
+
+    /// Writes the bookmarks into the scene file, keeping the objects already there
+    fn save_bookmarks(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut scene: SceneDescription = match std::fs::read_to_string(&self.scene_path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => SceneDescription::default(),
+            Err(err) => return Err(err.into()),
+        };
+        scene.bookmarks = self
+            .executor
+            .exec(CameraCommand::GetBookmarks)
+            .into_bookmarks()
+            .unwrap_or_default();
+        std::fs::write(&self.scene_path, serde_json::to_string_pretty(&scene)?)?;
+        Ok(())
+    }
+
+    /// Slice of a cloud texture as the scene samples it, with the histogram of the channel
+    fn noise_slice(&mut self, ui: &mut egui::Ui) {
+        let slice = &mut self.noise_slice;
+        ui.horizontal(|ui| {
+            for (texture, label) in CloudTexture::ALL
+                .into_iter()
+                .zip(["Форма", "Детали", "Погода"])
+            {
+                ui.radio_value(&mut slice.texture, texture, label);
+            }
+        });
+        ui.horizontal(|ui| {
+            for (plane, label) in SlicePlane::ALL.into_iter().zip(["XY", "XZ", "YZ"]) {
+                ui.radio_value(&mut slice.plane, plane, label);
+            }
+        });
+        ui.horizontal(|ui| {
+            for (channel, label) in ["R", "G", "B", "A"].into_iter().enumerate() {
+                ui.radio_value(&mut slice.channel, channel, label);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::widgets::Slider::new(&mut slice.depth, 0.0..=1.0));
+            ui.label("Положение среза");
+        });
+
+        let Some(img) = self
+            .executor
+            .exec(SceneCommand::GetNoiseSlice("cloud", self.noise_slice))
+            .into_noise_slice()
+        else {
+            return;
+        };
+        let pixels = img
+            .values
+            .iter()
+            .map(|v| Color32::from_gray((v.clamp(0.0, 1.0) * 255.0) as u8))
+            .collect();
+        let image = egui::ColorImage {
+            size: [img.size, img.size],
+            pixels,
+        };
+        let texture = ui
+            .ctx()
+            .load_texture("noise_slice", image, egui::TextureOptions::NEAREST);
+        let side = ui.available_width().min(256.0);
+        ui.add(egui::Image::new(&texture).fit_to_exact_size(egui::vec2(side, side)));
+        self.noise_slice_texture = Some(texture);
+
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(side, 64.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        let color =
+            [Color32::RED, Color32::GREEN, Color32::BLUE, Color32::GRAY][self.noise_slice.channel];
+        let most = img.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let bar = rect.width() / HISTOGRAM_BINS as f32;
+        for (i, &count) in img.histogram.iter().enumerate() {
+            let top = rect.bottom() - rect.height() * count as f32 / most;
+            let left = rect.left() + i as f32 * bar;
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(left, top),
+                    egui::pos2(left + bar - 1.0, rect.bottom()),
+                ),
+                0.0,
+                color,
+            );
+        }
+        let (min, max) = img
+            .values
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &v| {
+                (lo.min(v), hi.max(v))
+            });
+        let mean = img.values.iter().sum::<f32>() / img.values.len().max(1) as f32;
+        ui.label(format!("Мин. {min:.3}, макс. {max:.3}, среднее {mean:.3}"));
     }
 
     fn control(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
+            let projection = self.projection;
+            ui.horizontal(|ui| {
+                ui.label("Проекция");
+                for (kind, label) in ProjectionKind::ALL.into_iter().zip([
+                    "Перспективная",
+                    "Ортографическая",
+                    "Равнопромежуточная 360°",
+                ]) {
+                    ui.radio_value(&mut self.projection, kind, label);
+                }
+            });
+            if projection != self.projection {
+                self.executor
+                    .exec(CameraCommand::SetProjection(self.projection));
+            }
+            let controller = self.controller;
+            ui.horizontal(|ui| {
+                ui.label("Управление");
+                for (kind, label) in ControllerKind::ALL.into_iter().zip(["Орбита", "Полёт"])
+                {
+                    ui.radio_value(&mut self.controller, kind, label);
+                }
+            });
+            if self.controller == ControllerKind::Fly {
+                ui.label("WASD — движение, Q/E — вниз/вверх, колесо — скорость");
+            }
+            if controller != self.controller {
+                self.executor
+                    .exec(CameraCommand::SetController(self.controller));
+            }
+            ui.collapsing("Виды", |ui| self.camera_rig(ui));
+            ui.collapsing("Физическая камера", |ui| {
+                self.physical_camera(ui)
+            });
+            ui.collapsing("Закладки камеры", |ui| {
+                self.camera_bookmarks(ui)
+            });
+            ui.checkbox(&mut self.show_stats, "Статистика кадра");
+            let cloud_mode = self.cloud_mode;
+            ui.horizontal(|ui| {
+                ui.label("Рендер облаков");
+                for (mode, label) in
+                    CloudRenderMode::ALL
+                        .into_iter()
+                        .zip(["Полный", "Прогрессивный", "Репроекция"])
+                {
+                    ui.radio_value(&mut self.cloud_mode, mode, label);
+                }
+            });
+            if cloud_mode != self.cloud_mode {
+                self.executor
+                    .exec(DrawCommand::SetCloudRenderMode(self.cloud_mode));
+            }
+            ui.collapsing("Отладка облаков", |ui| {
+                let cloud_debug = self.cloud_debug;
+                for (view, label) in CloudDebugView::ALL.into_iter().zip([
+                    "Выключена",
+                    "Плотность",
+                    "Пропускание",
+                    "Пропускание к свету",
+                    "Фазовая функция",
+                    "Число шагов",
+                    "Карта погоды",
+                ]) {
+                    ui.radio_value(&mut self.cloud_debug, view, label);
+                }
+                if cloud_debug != self.cloud_debug {
+                    self.executor
+                        .exec(DrawCommand::SetCloudDebugView(self.cloud_debug));
+                }
+            });
+            ui.collapsing("Срезы шума", |ui| self.noise_slice(ui));
             ui.collapsing("Параметры облаков", |ui| {
                 ui.vertical(|ui| {
                     ui.vertical(|ui| {
-                        ui.horizontal(|ui| {
-                            let resp = ui.color_edit_button_srgba(&mut self.cloud.light_color);
-                            ui.label("Цвет облака");
-                            if resp.changed() {
-                                self.executor.exec(SceneCommand::SetLightColor(
-                                    "cloud",
-                                    self.cloud.light_color,
-                                ));
-                            }
-                        });
                         ui.horizontal(|ui| {
                             let resp = ui.add(egui::widgets::Slider::new(
                                 &mut self.move_vector.y,
@@ -475,7 +866,7 @@ impl App {
                     ui.vertical(|ui| {
                         ui.horizontal(|ui| {
                             let resp =
-                                ui.add(egui::widgets::Slider::new(&mut self.sun.1, 0.0..=179.0));
+                                ui.add(egui::widgets::Slider::new(&mut self.sun.1, -90.0..=180.0));
                             ui.label("Азумутальный угол");
                             if resp.changed() {
                                 self.executor.exec(SceneCommand::SetSunAngle(
@@ -486,7 +877,7 @@ impl App {
                         });
                         ui.horizontal(|ui| {
                             let resp =
-                                ui.add(egui::widgets::Slider::new(&mut self.sun.2, 0.0..=179.0));
+                                ui.add(egui::widgets::Slider::new(&mut self.sun.2, 0.0..=360.0));
                             ui.label("Зенитный угол");
                             if resp.changed() {
                                 self.executor.exec(SceneCommand::SetSunAngle(
@@ -495,9 +886,280 @@ impl App {
                                 ));
                             }
                         });
-                    })
+                        ui.horizontal(|ui| {
+                            let resp = ui.color_edit_button_srgba(&mut self.sun_color);
+                            ui.label("Цвет солнца");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetSunColor("sun", self.sun_color));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let resp = ui.add(
+                                egui::widgets::Slider::new(&mut self.sun_intensity, 0.0..=5.0)
+                                    .drag_value_speed(0.01),
+                            );
+                            ui.label("Интенсивность");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetSunIntensity("sun", self.sun_intensity));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let resp = ui.add(
+                                egui::widgets::Slider::new(&mut self.sun_diameter, 0.1..=10.0)
+                                    .drag_value_speed(0.01),
+                            );
+                            ui.label("Угловой диаметр");
+                            if resp.changed() {
+                                self.executor.exec(SceneCommand::SetSunAngularDiameter(
+                                    "sun",
+                                    self.sun_diameter,
+                                ));
+                            }
+                        });
+                    });
+                    ui.separator();
+                    ui.label("Время суток");
+                    let mut changed = false;
+                    ui.horizontal(|ui| {
+                        let time = &mut self.time_of_day;
+                        changed |= ui
+                            .add(egui::DragValue::new(&mut time.day).range(1..=31))
+                            .changed();
+                        changed |= ui
+                            .add(egui::DragValue::new(&mut time.month).range(1..=12))
+                            .changed();
+                        changed |= ui.add(egui::DragValue::new(&mut time.year)).changed();
+                        ui.label("Дата");
+                    });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(egui::widgets::Slider::new(
+                                &mut self.time_of_day.hours,
+                                0.0..=24.0,
+                            ))
+                            .changed();
+                        ui.label("Время, ч");
+                    });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(egui::widgets::Slider::new(
+                                &mut self.time_of_day.utc_offset,
+                                -12.0..=14.0,
+                            ))
+                            .changed();
+                        ui.label("Часовой пояс");
+                    });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(egui::widgets::Slider::new(
+                                &mut self.time_of_day.latitude,
+                                -90.0..=90.0,
+                            ))
+                            .changed();
+                        ui.label("Широта");
+                    });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(egui::widgets::Slider::new(
+                                &mut self.time_of_day.longitude,
+                                -180.0..=180.0,
+                            ))
+                            .changed();
+                        ui.label("Долгота");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.animate_day, "Анимация");
+                        ui.add(egui::widgets::Slider::new(&mut self.day_speed, 0.0..=6.0));
+                        ui.label("ч/с");
+                    });
+                    if changed {
+                        let time = self.time_of_day;
+                        self.time_of_day = time
+                            .with_date(time.year, time.month, time.day)
+                            .with_hours(time.hours);
+                        self.set_time_of_day();
+                    }
                 });
             });
+            ui.collapsing("Параметры луны", |ui| {
+                ui.vertical(|ui| {
+                    let mut angle_changed = false;
+                    ui.horizontal(|ui| {
+                        angle_changed |= ui
+                            .add(egui::widgets::Slider::new(
+                                &mut self.moon.elevation,
+                                -90.0..=90.0,
+                            ))
+                            .changed();
+                        ui.label("Высота");
+                    });
+                    ui.horizontal(|ui| {
+                        angle_changed |= ui
+                            .add(egui::widgets::Slider::new(
+                                &mut self.moon.azimuth,
+                                0.0..=360.0,
+                            ))
+                            .changed();
+                        ui.label("Азимут");
+                    });
+                    if angle_changed {
+                        self.executor.exec(SceneCommand::SetMoonAngle(
+                            "moon",
+                            (self.moon.elevation, self.moon.azimuth).into(),
+                        ));
+                    }
+                    ui.horizontal(|ui| {
+                        let resp = ui.add(
+                            egui::widgets::Slider::new(&mut self.moon.phase, 0.0..=1.0)
+                                .drag_value_speed(0.01),
+                        );
+                        ui.label("Фаза");
+                        if resp.changed() {
+                            self.executor
+                                .exec(SceneCommand::SetMoonPhase("moon", self.moon.phase));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let resp = ui.add(
+                            egui::widgets::Slider::new(&mut self.moon.intensity, 0.0..=1.0)
+                                .drag_value_speed(0.001),
+                        );
+                        ui.label("Интенсивность");
+                        if resp.changed() {
+                            self.executor
+                                .exec(SceneCommand::SetMoonIntensity("moon", self.moon.intensity));
+                        }
+                    });
+                });
+            });
+            ui.collapsing("Тональная компрессия", |ui| {
+                ui.vertical(|ui| {
+                    let operator = self.tone_mapping.operator;
+                    ui.horizontal(|ui| {
+                        for (op, label) in ToneMapOperator::ALL.into_iter().zip([
+                            "Без сжатия",
+                            "Рейнхард",
+                            "ACES",
+                            "Филмик",
+                        ]) {
+                            ui.radio_value(&mut self.tone_mapping.operator, op, label);
+                        }
+                    });
+                    if operator != self.tone_mapping.operator {
+                        self.executor
+                            .exec(DrawCommand::SetToneMapOperator(self.tone_mapping.operator));
+                    }
+                    ui.horizontal(|ui| {
+                        let resp = ui.add(
+                            egui::widgets::Slider::new(&mut self.tone_mapping.exposure, -5.0..=5.0)
+                                .drag_value_speed(0.01),
+                        );
+                        ui.label("Экспозиция");
+                        if resp.changed() {
+                            self.executor
+                                .exec(DrawCommand::SetExposure(self.tone_mapping.exposure));
+                        }
+                    });
+                });
+            });
+            ui.collapsing("Сглаживание", |ui| {
+                ui.vertical(|ui| {
+                    let supersampling = self.supersampling;
+                    ui.horizontal(|ui| {
+                        for (pattern, label) in SamplePattern::ALL
+                            .into_iter()
+                            .zip(["Регулярная сетка", "Повёрнутая сетка"])
+                        {
+                            ui.radio_value(&mut self.supersampling.pattern, pattern, label);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::widgets::Slider::new(
+                            &mut self.supersampling.grid,
+                            1..=4,
+                        ));
+                        ui.label("Отсчётов по стороне пикселя");
+                    });
+                    ui.checkbox(
+                        &mut self.supersampling.cloud_jitter,
+                        "Джиттер отсчётов облаков",
+                    );
+                    if supersampling != self.supersampling {
+                        self.executor
+                            .exec(DrawCommand::SetSupersampling(self.supersampling));
+                    }
+                });
+            });
+            ui.collapsing("Параметры неба", |ui| {
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        let resp = ui.add(
+                            egui::widgets::Slider::new(&mut self.sky.turbidity, 1.0..=10.0)
+                                .drag_value_speed(0.01),
+                        );
+                        ui.label("Мутность");
+                        if resp.changed() {
+                            self.executor
+                                .exec(SceneCommand::SetSkyTurbidity("sky", self.sky.turbidity));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut albedo = self.sky.ground_albedo.to_array();
+                        let resp = ui.color_edit_button_rgb(&mut albedo);
+                        ui.label("Альбедо поверхности");
+                        if resp.changed() {
+                            self.sky.ground_albedo = albedo.into();
+                            self.executor.exec(SceneCommand::SetSkyGroundAlbedo(
+                                "sky",
+                                self.sky.ground_albedo,
+                            ));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut radius = self.sky.planet_radius / 1000.0;
+                        let resp = ui.add(
+                            egui::widgets::Slider::new(&mut radius, 100.0..=10000.0)
+                                .logarithmic(true),
+                        );
+                        ui.label("Радиус планеты, км");
+                        if resp.changed() {
+                            self.sky.planet_radius = radius * 1000.0;
+                            self.executor.exec(SceneCommand::SetSkyPlanetRadius(
+                                "sky",
+                                self.sky.planet_radius,
+                            ));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let resp = ui.add(
+                            egui::widgets::Slider::new(&mut self.sky.aerial_strength, 0.0..=20.0)
+                                .drag_value_speed(0.01),
+                        );
+                        ui.label("Воздушная перспектива");
+                        if resp.changed() {
+                            self.executor.exec(SceneCommand::SetSkyAerialStrength(
+                                "sky",
+                                self.sky.aerial_strength,
+                            ));
+                        }
+                    });
+                });
+            });
+            ui.collapsing("Отладка ландшафта", |ui| {
+                let terrain_debug = self.terrain_debug;
+                ui.checkbox(&mut self.terrain_debug.wireframe, "Каркас");
+                ui.checkbox(&mut self.terrain_debug.normals, "Нормали вершин");
+                ui.checkbox(
+                    &mut self.terrain_debug.bounding_box,
+                    "Ограничивающий параллелепипед",
+                );
+                if terrain_debug != self.terrain_debug {
+                    self.executor
+                        .exec(DrawCommand::SetTerrainDebug(self.terrain_debug));
+                }
+            });
             ui.collapsing("Параметры ландшафта", |ui| {
                 ui.vertical(|ui| {
                     ui.vertical(|ui| {
@@ -690,7 +1352,34 @@ struct App {
     noise_mode: NoiseMode,
     cloud: CloudBuilder,
     terrain: TerrainBuilder,
+    sky: SkyBuilder,
     sun: (f32, f32, f32),
+    sun_color: Color32,
+    sun_intensity: f32,
+    sun_diameter: f32,
+    moon: Moon,
+    tone_mapping: ToneMapping,
+    projection: ProjectionKind,
+    controller: ControllerKind,
+    physical: PhysicalCamera,
+    rig: CameraRig,
+    /// Distance between the cameras of the rig
+    rig_spacing: f32,
+    bookmark_name: String,
+    /// Scene description the bookmarks are loaded from and written to
+    scene_path: String,
+    bookmark_error: Option<String>,
+    cloud_mode: CloudRenderMode,
+    show_stats: bool,
+    cloud_debug: CloudDebugView,
+    terrain_debug: TerrainDebug,
+    supersampling: Supersampling,
+    noise_slice: NoiseSlice,
+    /// Keeps the slice texture alive while egui paints it
+    noise_slice_texture: Option<egui::TextureHandle>,
+    time_of_day: TimeOfDay,
+    animate_day: bool,
+    day_speed: f32,
     background_color: Color32,
     offset_speed: Vec3,
     move_vector: Vec3,
@@ -740,7 +1429,6 @@ impl App {
             .with_color(Color32::WHITE)
             .with_col_a(Color32::WHITE)
             .with_col_b(Color32::LIGHT_BLUE)
-            .with_light_color(Color32::WHITE)
             .with_light_absorption_through_cloud(0.6)
             .with_light_absorption_toward_sun(0.6)
             .with_phase_params(Vec4::new(0.00, 0.48, 0.37, 0.34))
@@ -764,7 +1452,9 @@ impl App {
 
         let mut executor = Facade::default();
 
-        let sun = Sun::new(10.0, -90.0, -90.0);
+        let sun = Sun::new(10.0, -90.0, -90.0).with_angular_diameter(2.0);
+        let moon = Moon::new(30.0, 90.0, 0.5);
+        let sky_params = SkyBuilder::new().with_turbidity(2.0);
 
         let terrain_params = TerrainBuilder::default()
             .with_bounding_box((Vec3::new(-2.5, 0.0, -2.5), Vec3::new(2.5, 0.5, 2.5)))
@@ -790,6 +1480,8 @@ impl App {
         executor.exec(SceneCommand::AddObject("grid", Grid::new(10, 1.0).into()));
         executor.exec(CameraCommand::SetCamera(Camera::default()));
         executor.exec(SceneCommand::AddObject("sun", sun.into()));
+        executor.exec(SceneCommand::AddObject("moon", moon.into()));
+        executor.exec(SceneCommand::AddObject("sky", sky_params.build().into()));
         executor.exec(SceneCommand::AddObject(
             "cloud",
             cloud_params.build().into(),
@@ -799,15 +1491,42 @@ impl App {
             terrain_params.build().into(),
         ));
 
-        Self {
+        let mut app = Self {
             executor,
             noise_mode: NoiseMode::Detail,
             cloud: cloud_params,
             terrain: terrain_params,
+            sky: sky_params,
             background_color: Color32::LIGHT_BLUE,
             offset_speed: Vec3::new(1.0, 0.0, 1.0),
             sun: (sun.d, sun.a.abs(), sun.z.abs()),
+            sun_color: sun.color,
+            sun_intensity: sun.intensity,
+            sun_diameter: sun.angular_diameter,
+            moon,
+            tone_mapping: ToneMapping::default(),
+            projection: ProjectionKind::default(),
+            controller: ControllerKind::default(),
+            physical: PhysicalCamera::default(),
+            rig: CameraRig::default(),
+            rig_spacing: 0.5,
+            bookmark_name: String::new(),
+            scene_path: String::from("render/scenes/scene.json"),
+            bookmark_error: None,
+            cloud_mode: CloudRenderMode::default(),
+            show_stats: false,
+            cloud_debug: CloudDebugView::default(),
+            terrain_debug: TerrainDebug::default(),
+            supersampling: Supersampling::default(),
+            noise_slice: NoiseSlice::default(),
+            noise_slice_texture: None,
+            time_of_day: TimeOfDay::default(),
+            animate_day: false,
+            day_speed: 1.0,
             move_vector: Vec3::ZERO,
-        }
+        };
+        // The bookmarks of the last session, if they were written
+        app.bookmark_error = app.load_bookmarks().err().map(|err| err.to_string());
+        app
     }
 }